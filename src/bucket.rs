@@ -0,0 +1,145 @@
+use crate::logic::BucketMode;
+use crate::template::FolderTemplate;
+use crate::transfer::{self, LinkMode};
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Name of the built-in bucket pictures are copied to for edits
+pub const EDIT: &str = "edit";
+/// Name of the built-in bucket deleted pictures go to
+pub const BIN: &str = "bin";
+
+/// A named destination for pictures, triggered by a key
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub struct Bucket {
+    pub name: String,
+    pub key: String,
+    pub mode: BucketMode,
+    /// destination, relative to the browsed folder unless absolute
+    pub folder: FolderTemplate,
+}
+
+impl Bucket {
+    /// Built-in bucket pictures are copied to, with `e` unless remapped
+    pub fn edit(folder: FolderTemplate) -> Self {
+        Self {
+            name: EDIT.into(),
+            key: "e".into(),
+            mode: BucketMode::Copy,
+            folder,
+        }
+    }
+
+    /// Built-in bucket pictures are moved to, with `d` unless remapped
+    pub fn bin(folder: FolderTemplate) -> Self {
+        Self {
+            name: BIN.into(),
+            key: "d".into(),
+            mode: BucketMode::Move,
+            folder,
+        }
+    }
+}
+
+impl FromStr for Bucket {
+    type Err = String;
+
+    /// Parses `NAME:KEY:MODE[:PATH]`, PATH (a folder template) defaulting to a NAME subfolder
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(4, ':');
+        let (Some(name), Some(key), Some(mode)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err("expected NAME:KEY:MODE[:PATH]".into());
+        };
+        if name.is_empty() {
+            return Err("bucket name is empty".into());
+        }
+        if name == EDIT || name == BIN {
+            return Err(format!("bucket name '{}' is already used by phog", name));
+        }
+        if key.chars().count() != 1 {
+            return Err(format!("key '{}' should be a single character", key));
+        }
+        let mode = match mode.to_lowercase().as_str() {
+            "copy" => BucketMode::Copy,
+            "move" => BucketMode::Move,
+            "link" => BucketMode::Link,
+            "hardlink" => BucketMode::Hardlink,
+            _ => {
+                return Err(format!(
+                    "unknown mode '{}' (copy, move, link or hardlink)",
+                    mode
+                ))
+            }
+        };
+        let folder = parts.next().unwrap_or(name).parse()?;
+        Ok(Self {
+            name: name.into(),
+            key: key.into(),
+            mode,
+            folder,
+        })
+    }
+}
+
+impl TryFrom<String> for Bucket {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<LinkMode> for BucketMode {
+    fn from(mode: LinkMode) -> Self {
+        match mode {
+            LinkMode::Copy => BucketMode::Copy,
+            LinkMode::Hardlink => BucketMode::Hardlink,
+            LinkMode::Symlink => BucketMode::Link,
+        }
+    }
+}
+
+/// Copies, moves or links a file according to the mode (trash is not a transfer).
+/// Returns the mode used, a link falling back to a copy when it can't be made
+pub fn transfer(mode: BucketMode, from: &Path, to: &Path) -> io::Result<BucketMode> {
+    let link_mode = match mode {
+        BucketMode::Copy => LinkMode::Copy,
+        BucketMode::Hardlink => LinkMode::Hardlink,
+        BucketMode::Link => LinkMode::Symlink,
+        BucketMode::Move => return transfer::move_file(from, to).map(|_| mode),
+        BucketMode::Trash => return Err(io::ErrorKind::Unsupported.into()),
+    };
+    transfer::link_or_copy(link_mode, from, to).map(BucketMode::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_buckets() {
+        let bucket: Bucket = "client:c:copy".parse().unwrap();
+        assert_eq!(bucket.name, "client");
+        assert_eq!(bucket.key, "c");
+        assert_eq!(bucket.mode, BucketMode::Copy);
+        assert_eq!(bucket.folder.to_string(), "client");
+        let bucket: Bucket = "print:p:LINK:/mnt/print:a".parse().unwrap();
+        assert_eq!(bucket.mode, BucketMode::Link);
+        assert_eq!(bucket.folder.to_string(), "/mnt/print:a");
+        let bucket: Bucket = "proof:p:hardlink".parse().unwrap();
+        assert_eq!(bucket.mode, BucketMode::Hardlink);
+        for wrong in [
+            "client:c",
+            ":c:copy",
+            "edit:c:copy",
+            "client:cc:copy",
+            "client:c:paste",
+            "client:c:softlink",
+            "client:c:copy:{week}",
+        ] {
+            assert!(wrong.parse::<Bucket>().is_err(), "{}", wrong);
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use crate::transfer::staging_path;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Name of the checksum manifest kept in a folder, in the format of `b3sum`
+/// (`b3sum -c` can check it too)
+pub const MANIFEST_NAME: &str = "phog-checksums.b3";
+
+/// State of a file listed in a manifest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileCheck {
+    /// content matches the recorded checksum
+    Ok,
+    /// content changed since it was recorded
+    Corrupted,
+    /// file can't be read anymore
+    Missing,
+}
+
+/// BLAKE3 hash of a file content
+pub fn hash_file(path: &Path) -> io::Result<blake3::Hash> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(fs::File::open(path)?)?;
+    Ok(hasher.finalize())
+}
+
+/// Checks that a file has the given content hash
+pub fn matches(path: &Path, hash: &blake3::Hash) -> io::Result<bool> {
+    Ok(hash_file(path)? == *hash)
+}
+
+/// Records the hash of a file in the manifest of its folder, replacing any previous entry
+pub fn record(path: &Path, hash: &blake3::Hash) -> io::Result<()> {
+    let (Some(folder), Some(name)) = (path.parent(), path.file_name()) else {
+        return Err(io::ErrorKind::InvalidInput.into());
+    };
+    let name = name.to_string_lossy();
+    let mut entries = read_manifest(folder)?;
+    entries.retain(|(_, entry)| *entry != name);
+    entries.push((hash.to_hex().to_string(), name.into_owned()));
+    // written next to it then renamed, so an interrupted write can't lose the manifest
+    let path = folder.join(MANIFEST_NAME);
+    let staged = staging_path(&path);
+    let written = fs::File::create(&staged).and_then(|mut manifest| {
+        for (hash, name) in entries {
+            writeln!(manifest, "{}  {}", hash, name)?;
+        }
+        manifest.sync_all()
+    });
+    if let Err(err) = written.and_then(|_| fs::rename(&staged, &path)) {
+        let _ = fs::remove_file(&staged);
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// Checks every file listed in the manifest of a folder
+pub fn verify_folder(folder: &Path) -> io::Result<Vec<(String, FileCheck)>> {
+    let entries = read_manifest(folder)?;
+    if entries.is_empty() && !folder.join(MANIFEST_NAME).exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no {} in {}", MANIFEST_NAME, folder.display()),
+        ));
+    }
+    Ok(entries
+        .into_iter()
+        .map(|(hash, name)| {
+            let check = match hash_file(&folder.join(&name)) {
+                Ok(actual) if actual.to_hex().as_str() == hash => FileCheck::Ok,
+                Ok(_) => FileCheck::Corrupted,
+                Err(_) => FileCheck::Missing,
+            };
+            (name, check)
+        })
+        .collect())
+}
+
+/// Entries (hash, file name) of the manifest of a folder, none if there is no manifest yet
+pub(crate) fn read_manifest(folder: &Path) -> io::Result<Vec<(String, String)>> {
+    let content = match fs::read_to_string(folder.join(MANIFEST_NAME)) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+    Ok(content
+        .lines()
+        .filter_map(|line| line.split_once("  "))
+        .map(|(hash, name)| (hash.to_string(), name.to_string()))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_and_verify() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a.jpg"), dir.path().join("b.jpg"));
+        fs::write(&a, "a").unwrap();
+        fs::write(&b, "b").unwrap();
+        record(&a, &hash_file(&a).unwrap()).unwrap();
+        record(&b, &hash_file(&b).unwrap()).unwrap();
+        // recorded again after a change, replacing the entry
+        fs::write(&a, "new").unwrap();
+        record(&a, &hash_file(&a).unwrap()).unwrap();
+        let entries = read_manifest(dir.path()).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].1, "a.jpg");
+        // only the manifest, no temporary file left
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 3);
+
+        fs::write(&b, "corrupted").unwrap();
+        fs::remove_file(&a).unwrap();
+        let checks = verify_folder(dir.path()).unwrap();
+        assert_eq!(
+            checks,
+            [
+                ("b.jpg".to_string(), FileCheck::Corrupted),
+                ("a.jpg".to_string(), FileCheck::Missing)
+            ]
+        );
+    }
+
+    #[test]
+    fn verify_without_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(read_manifest(dir.path()).unwrap().is_empty());
+        let err = verify_folder(dir.path()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
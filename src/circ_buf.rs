@@ -1,16 +1,21 @@
-/// total number of images loaded in buffer
-const BUFFER_SIZE: usize = 8;
+/// default total number of images loaded in buffer
+pub const BUFFER_SIZE: usize = 8;
 
-/// Minimum number of elements to carry on either side of the buffer
-const MIN_ELEM_NUM: usize = (BUFFER_SIZE - 2) / 2;
+/// smallest buffer keeping at least one element on either side of the current one
+pub const MIN_BUFFER_SIZE: usize = 4;
 
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use exif::Tag;
+use image::metadata::Orientation;
 use slint::{Image, Rgb8Pixel, SharedPixelBuffer};
 use std::fs;
-use std::time::Instant;
-use std::{cmp::min, path::PathBuf};
+use std::time::{Instant, SystemTime};
+use std::{
+    cmp::{max, min},
+    path::{Path, PathBuf},
+};
 use tokio::spawn;
 use tokio::sync::Mutex;
 use turbojpeg::image::Rgb;
@@ -18,11 +23,34 @@ use turbojpeg::image::Rgb;
 // use image::SharedPixelBuffer;
 use turbojpeg::{Transform, TransformOp};
 
+use crate::error::PhogError;
+
+/// Modification time and size of a file, used to know if it changed since it was loaded
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FileStamp {
+    modified: SystemTime,
+    size: u64,
+}
+
+impl FileStamp {
+    fn from_metadata(metadata: &fs::Metadata) -> Option<Self> {
+        Some(Self {
+            modified: metadata.modified().ok()?,
+            size: metadata.len(),
+        })
+    }
+}
+
 /// Image element with logic to load and read data as fast as possible
 #[derive(Debug, Clone)]
 struct ImageElement {
     raw_img: SharedPixelBuffer<Rgb8Pixel>,
     file_name: String,
+    path: PathBuf,
+    /// state of the file when it was loaded
+    stamp: Option<FileStamp>,
+    /// set if the last load failed, raw_img should not be displayed then
+    error: Option<PhogError>,
 }
 impl Default for ImageElement {
     fn default() -> Self {
@@ -33,6 +61,9 @@ impl Default for ImageElement {
         Self {
             raw_img: SharedPixelBuffer::new(1, 1),
             file_name: String::default(),
+            path: PathBuf::default(),
+            stamp: None,
+            error: None,
         }
     }
 }
@@ -42,33 +73,52 @@ impl ImageElement {
         Image::from_rgb8(self.raw_img.clone())
     }
 
+    /// Loads the image, keeping the error to be displayed if it failed
     pub fn load(&mut self, elem: PathBuf) {
-        self.file_name = elem.file_name().unwrap().to_str().unwrap().to_string();
+        self.file_name = elem
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        self.stamp = fs::metadata(&elem)
+            .ok()
+            .and_then(|metadata| FileStamp::from_metadata(&metadata));
+        self.error = self.decode(&elem).err();
+        self.path = elem;
+    }
+
+    fn decode(&mut self, elem: &Path) -> Result<(), PhogError> {
         // reading EXIF to get orient (<10ms)
-        let file = fs::File::open(elem.clone()).unwrap();
+        let file = fs::File::open(elem)?;
         let mut buffer_reader = std::io::BufReader::new(&file);
         let exif_reader = exif::Reader::new();
         let exif_res = exif_reader.read_from_container(&mut buffer_reader);
-        let mut rotate = None;
+        let mut orientation = 1;
         if let Ok(exif) = exif_res {
-            if let Some(orientation) = exif.get_field(Tag::Orientation, exif::In::PRIMARY) {
-                rotate = match orientation.value.get_uint(0) {
-                    Some(1) => None,                                     // in landscape
-                    Some(3) => Some(Transform::op(TransformOp::Rot180)), // in landscape upside down
-                    Some(6) => Some(Transform::op(TransformOp::Rot90)),  // in portrait
-                    Some(8) => Some(Transform::op(TransformOp::Rot270)), // in portrait flipped
-                    _ => None, // Could not determine orientation based on EXIF data
-                }
+            if let Some(field) = exif.get_field(Tag::Orientation, exif::In::PRIMARY) {
+                orientation = field.value.get_uint(0).unwrap_or(1);
             }
         }
 
-        // decoding and rotating if needed (100ms + 400ms)
-        let data = fs::read(elem).unwrap();
-        let decoded = if let Some(transform) = rotate {
-            let o = turbojpeg::transform(&transform, &data).unwrap();
-            turbojpeg::decompress_image::<Rgb<u8>>(&o).unwrap()
+        let is_jpeg = elem
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("jpg") || ext.eq_ignore_ascii_case("jpeg"));
+        let decoded = if is_jpeg {
+            // decoding and rotating if needed (100ms + 400ms)
+            let data = fs::read(elem)?;
+            if let Some(transform) = jpeg_transform(orientation) {
+                let o = turbojpeg::transform(&transform, &data)?;
+                turbojpeg::decompress_image::<Rgb<u8>>(&o)?
+            } else {
+                turbojpeg::decompress_image::<Rgb<u8>>(&data)?
+            }
         } else {
-            turbojpeg::decompress_image::<Rgb<u8>>(&data).unwrap()
+            // other formats go through the image crate, orientation applied after decoding
+            let mut img = image::open(elem)?;
+            if let Some(orient) = Orientation::from_exif(orientation as u8) {
+                img.apply_orientation(orient);
+            }
+            img.into_rgb8()
         };
 
         // if self.raw_img.width() == decoded.height() && self.raw_img.height() == decoded.width() {
@@ -78,7 +128,7 @@ impl ImageElement {
         if self.raw_img.width() != decoded.width() || self.raw_img.height() != decoded.height() {
             self.raw_img =
                 SharedPixelBuffer::clone_from_slice(&decoded, decoded.width(), decoded.height());
-            return;
+            return Ok(());
         }
 
         // Actual copying rather fast (10ms)
@@ -86,12 +136,31 @@ impl ImageElement {
         unsafe {
             std::ptr::copy_nonoverlapping(decoded.as_ptr(), img_data.as_mut_ptr(), decoded.len());
         }
+        Ok(())
     }
 }
 
+/// Lossless JPEG transform matching an EXIF orientation value (None if nothing to do)
+fn jpeg_transform(orientation: u32) -> Option<Transform> {
+    let op = match orientation {
+        2 => TransformOp::Hflip,      // mirrored
+        3 => TransformOp::Rot180,     // in landscape upside down
+        4 => TransformOp::Vflip,      // upside down mirrored
+        5 => TransformOp::Transpose,  // in portrait mirrored
+        6 => TransformOp::Rot90,      // in portrait
+        7 => TransformOp::Transverse, // in portrait flipped mirrored
+        8 => TransformOp::Rot270,     // in portrait flipped
+        _ => return None,             // in landscape or could not determine orientation
+    };
+    Some(Transform::op(op))
+}
+
 pub struct ImageStat {
     pub image: Image,
     pub name: String,
+    pub path: PathBuf,
+    /// reason why the image could not be loaded
+    pub error: Option<PhogError>,
     pub number: usize,
     pub out_of: usize,
 }
@@ -105,9 +174,13 @@ pub struct CircularBuffer {
     /// list of all path to rotate between
     pic_list: Vec<PathBuf>,
     /// Actual buffered elements
-    buffer: [Arc<Mutex<ImageElement>>; BUFFER_SIZE],
-    /// true size of buffer (might be smaller than BUFFER_SIZE if total amount of elements are smaller)
+    buffer: Vec<Arc<Mutex<ImageElement>>>,
+    /// number of loads queued or running on each buffered element
+    loading: Vec<Arc<AtomicUsize>>,
+    /// true size of buffer (might be smaller than buffer.len() if total amount of elements are smaller)
     true_size: usize,
+    /// Minimum number of elements to carry on either side of the buffer
+    min_elem_num: usize,
     /// used indices of the buffer array
     /// to avoid unnecessary copies when an item is deleted and there is not enough items left to fill
     indices: Vec<usize>,
@@ -120,24 +193,34 @@ pub struct CircularBuffer {
 }
 
 impl CircularBuffer {
-    pub fn new(pic_list: Vec<PathBuf>) -> Self {
-        let true_size = min(BUFFER_SIZE, pic_list.len());
+    /// Buffer of size elements (at least MIN_BUFFER_SIZE) over pic_list
+    pub fn new(pic_list: Vec<PathBuf>, size: usize) -> Result<Self, PhogError> {
+        if pic_list.is_empty() {
+            return Err(PhogError::EmptyFolder);
+        }
+        let size = max(size, MIN_BUFFER_SIZE);
+        let true_size = min(size, pic_list.len());
         let indices: Vec<usize> = (0..true_size).collect();
         let a = Instant::now();
-        let buffer = [(); BUFFER_SIZE].map(|_| Arc::new(Mutex::new(ImageElement::default())));
+        let buffer: Vec<_> = (0..size)
+            .map(|_| Arc::new(Mutex::new(ImageElement::default())))
+            .collect();
+        let loading: Vec<_> = (0..size).map(|_| Arc::new(AtomicUsize::new(0))).collect();
         buffer[0].blocking_lock().load(pic_list[0].clone());
 
         println!("First img loaded ({:?})", Instant::now() - a);
-        Self {
+        Ok(Self {
             counter: 0,
             pic_list,
             buffer,
+            loading,
             true_size,
+            min_elem_num: (size - 2) / 2,
             indices,
             current_idx: 0,
             front_file: true_size - 1,
             back_file: 0,
-        }
+        })
     }
 
     /// helper fn to increase by one current_idx
@@ -157,6 +240,11 @@ impl CircularBuffer {
     fn back_buffer_idx(&self) -> usize {
         self.indices[(self.current_idx + self.true_size - self.back_file) % self.true_size]
     }
+    /// buffer index of a loaded element of pic_list
+    fn buffer_idx_of(&self, index: usize) -> usize {
+        let pos = self.current_idx + self.true_size + index - self.counter;
+        self.indices[pos % self.true_size]
+    }
 
     /// Spawns loads on all buffer in parallel
     pub async fn init(&mut self) {
@@ -173,8 +261,8 @@ impl CircularBuffer {
         self.incr_idx();
         self.counter += 1;
 
-        let front_buf_full = self.front_file > MIN_ELEM_NUM;
-        let no_more_elems = self.counter + MIN_ELEM_NUM >= self.pic_list.len();
+        let front_buf_full = self.front_file > self.min_elem_num;
+        let no_more_elems = self.counter + self.min_elem_num >= self.pic_list.len();
         if front_buf_full || no_more_elems {
             self.front_file -= 1;
             self.back_file += 1;
@@ -191,7 +279,7 @@ impl CircularBuffer {
         }
         self.decr_idx();
         self.counter -= 1;
-        let back_buf_full = self.back_file > MIN_ELEM_NUM;
+        let back_buf_full = self.back_file > self.min_elem_num;
         let no_more_elems = self.counter < self.back_file;
         if back_buf_full || no_more_elems {
             self.back_file -= 1;
@@ -212,9 +300,12 @@ impl CircularBuffer {
     }
     async fn load(&mut self, elem: PathBuf, buf_pos: usize) {
         let a = Arc::clone(&self.buffer[buf_pos]);
+        let loading = Arc::clone(&self.loading[buf_pos]);
+        loading.fetch_add(1, Ordering::AcqRel);
         spawn(async move {
             let mut a_lock = a.lock().await;
             a_lock.load(elem);
+            loading.fetch_sub(1, Ordering::AcqRel);
         });
     }
 
@@ -227,7 +318,7 @@ impl CircularBuffer {
         }
 
         let buf_idx = self.current_buffer_idx();
-        if self.pic_list.len() < BUFFER_SIZE {
+        if self.pic_list.len() < self.buffer.len() {
             // nothing to fill buffer with -> removing current buf idx from indices
             self.indices.retain(|value| *value != buf_idx);
             self.true_size -= 1;
@@ -237,6 +328,8 @@ impl CircularBuffer {
                 self.decr_idx();
                 return true;
             }
+            // next element now at current_idx, unless current was the last of indices
+            self.current_idx %= self.true_size;
             self.front_file -= 1;
             return true;
         }
@@ -257,7 +350,9 @@ impl CircularBuffer {
         }
         self.indices[(self.current_idx + self.front_file) % self.true_size] = buf_idx;
 
-        let all_front_loaded = self.counter + MIN_ELEM_NUM >= self.pic_list.len();
+        // front might already reach the end of the list if it is longer than min_elem_num
+        let all_front_loaded =
+            self.counter + max(self.min_elem_num, self.front_file) >= self.pic_list.len();
         if all_front_loaded {
             self.front_file -= 1;
             self.back_file += 1;
@@ -269,6 +364,229 @@ impl CircularBuffer {
         true
     }
 
+    /// Adds an element at the end of the list, loading it right away if it is close to the current one
+    pub async fn push(&mut self, elem: PathBuf) {
+        self.pic_list.push(elem);
+        let last = self.pic_list.len() - 1;
+        if self.true_size < self.buffer.len() {
+            // everything is loaded -> using an unused buffer for the new element
+            if let Some(buf_idx) = (0..self.buffer.len()).find(|i| !self.indices.contains(i)) {
+                self.insert_front(buf_idx);
+                self.load_elem_front().await;
+            }
+        } else if self.counter + self.front_file + 1 == last && self.back_file > self.min_elem_num {
+            // front is not full -> recycling the last element of the back
+            self.front_file += 1;
+            self.back_file -= 1;
+            self.load_elem_front().await;
+        }
+    }
+
+    /// Removes any element of the list (returns false if the list is now empty)
+    pub async fn remove(&mut self, index: usize) -> bool {
+        if index == self.counter {
+            return self.delete().await;
+        }
+        let in_back = index < self.counter;
+        let offset = index.abs_diff(self.counter);
+        let loaded = offset <= if in_back { self.back_file } else { self.front_file };
+        self.pic_list.remove(index);
+        if in_back {
+            self.counter -= 1;
+        }
+        if !loaded {
+            return true;
+        }
+
+        // freeing the buffer of the removed element
+        let pos = if in_back {
+            (self.current_idx + self.true_size - offset) % self.true_size
+        } else {
+            (self.current_idx + offset) % self.true_size
+        };
+        let buf_idx = self.indices.remove(pos);
+        if pos < self.current_idx {
+            self.current_idx -= 1;
+        }
+        self.true_size -= 1;
+        if in_back {
+            self.back_file -= 1;
+        } else {
+            self.front_file -= 1;
+        }
+        if self.pic_list.len() <= self.true_size {
+            // every element left is still loaded
+            return true;
+        }
+
+        // refilling from the side that has the fewer elements loaded
+        let front_available = self.counter + self.front_file + 1 < self.pic_list.len();
+        let back_available = self.counter > self.back_file;
+        if front_available && (self.front_file <= self.back_file || !back_available) {
+            self.insert_front(buf_idx);
+            self.load_elem_front().await;
+        } else {
+            self.insert_back(buf_idx);
+            self.load_elem_back().await;
+        }
+        true
+    }
+
+    /// Checks loaded elements against the disk: elements whose file disappeared are removed
+    /// and elements whose file changed are reloaded (returns false if the list is now empty)
+    pub async fn sync(&mut self) -> bool {
+        let first = self.counter - self.back_file;
+        let last = self.counter + self.front_file;
+        // from the end so removing an element does not move the ones left to check
+        for index in (first..=last).rev() {
+            if !self.sync_elem(index).await {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Checks the current element against the disk, the next one taking its place if its file
+    /// disappeared (returns false if the list is now empty)
+    pub async fn sync_current(&mut self) -> bool {
+        loop {
+            let len = self.pic_list.len();
+            if !self.sync_elem(self.counter).await {
+                return false;
+            }
+            if self.pic_list.len() == len {
+                return true;
+            }
+        }
+    }
+
+    /// Removes the element at index if its file disappeared, reloads it if its file changed.
+    /// Elements being loaded are left alone, their load reading the file as it is now
+    async fn sync_elem(&mut self, index: usize) -> bool {
+        let path = self.pic_list[index].clone();
+        let stamp = match fs::metadata(&path) {
+            Ok(metadata) => FileStamp::from_metadata(&metadata),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                println!("{} was removed from the disk", path.display());
+                return self.remove(index).await;
+            }
+            Err(_) => return true,
+        };
+        let buf_pos = self.buffer_idx_of(index);
+        if self.loading[buf_pos].load(Ordering::Acquire) > 0 {
+            return true;
+        }
+        let Ok(elem) = self.buffer[buf_pos].try_lock() else {
+            return true;
+        };
+        let changed = elem.path != path || elem.stamp != stamp;
+        drop(elem);
+        if changed {
+            self.load(path, buf_pos).await;
+        }
+        true
+    }
+
+    /// Index in the list of the element with this path
+    pub fn position(&self, path: &Path) -> Option<usize> {
+        self.pic_list.iter().position(|elem| elem == path)
+    }
+
+    /// Paths of all the elements, in order
+    pub fn pic_list(&self) -> &[PathBuf] {
+        &self.pic_list
+    }
+
+    /// Follows the file of an element to its new path, keeping it loaded
+    pub async fn rename(&mut self, index: usize, path: PathBuf) {
+        let loaded =
+            index + self.back_file >= self.counter && index <= self.counter + self.front_file;
+        if loaded {
+            let mut elem = self.buffer[self.buffer_idx_of(index)].lock().await;
+            if elem.path == self.pic_list[index] {
+                elem.file_name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                elem.path = path.clone();
+            }
+        }
+        self.pic_list[index] = path;
+    }
+
+    /// Switches current element to the last one of the list
+    pub async fn go_to_last(&mut self) {
+        self.go_to(self.pic_list.len() - 1).await;
+    }
+
+    /// Switches current element to the first one of the list
+    pub async fn go_to_first(&mut self) {
+        self.go_to(0).await;
+    }
+
+    /// Switches current element to the one at index, reloading the whole buffer if it was not loaded
+    pub async fn go_to(&mut self, index: usize) {
+        let loaded =
+            index + self.back_file >= self.counter && index <= self.counter + self.front_file;
+        if loaded {
+            while self.counter < index {
+                self.next_img().await;
+            }
+            while self.counter > index {
+                self.prev_img().await;
+            }
+            return;
+        }
+        self.reload(index).await;
+    }
+
+    /// Browses the same pictures in another order, switching to the one at index
+    pub async fn reorder(&mut self, pic_list: Vec<PathBuf>, index: usize) {
+        self.pic_list = pic_list;
+        self.reload(index).await;
+    }
+
+    /// Loads the whole buffer around the element at index, which becomes the current one
+    async fn reload(&mut self, index: usize) {
+        self.counter = index;
+        self.back_file = min(index, self.min_elem_num);
+        self.front_file = self.true_size - 1 - self.back_file;
+        let last = self.pic_list.len() - 1;
+        if self.counter + self.front_file > last {
+            self.front_file = last - self.counter;
+            self.back_file = self.true_size - 1 - self.front_file;
+        }
+        // starting by the current element so it is displayed as soon as possible
+        let first = self.counter - self.back_file;
+        for i in 0..self.true_size {
+            let offset = (self.back_file + i) % self.true_size;
+            let buf_pos = self.indices[(self.current_idx + self.true_size - self.back_file + offset) % self.true_size];
+            self.load(self.pic_list[first + offset].clone(), buf_pos).await;
+        }
+    }
+
+    /// inserts an unused buffer right after the front element
+    fn insert_front(&mut self, buf_idx: usize) {
+        let pos = (self.current_idx + self.front_file) % self.true_size + 1;
+        if pos <= self.current_idx {
+            self.current_idx += 1;
+        }
+        self.indices.insert(pos, buf_idx);
+        self.true_size += 1;
+        self.front_file += 1;
+    }
+
+    /// inserts an unused buffer right before the back element
+    fn insert_back(&mut self, buf_idx: usize) {
+        let pos = (self.current_idx + self.true_size - self.back_file) % self.true_size;
+        if pos <= self.current_idx {
+            self.current_idx += 1;
+        }
+        self.indices.insert(pos, buf_idx);
+        self.true_size += 1;
+        self.back_file += 1;
+    }
+
     /// reading element and returning stats
     pub async fn get_elem(&self) -> ImageStat {
         if false {
@@ -291,6 +609,8 @@ impl CircularBuffer {
         ImageStat {
             image: elem.read(),
             name: elem.file_name.clone(),
+            path: elem.path.clone(),
+            error: elem.error.clone(),
             number: self.counter + 1,
             out_of: self.pic_list.len(),
         }
@@ -302,8 +622,284 @@ impl CircularBuffer {
         ImageStat {
             image: elem.read(),
             name: elem.file_name.clone(),
+            path: elem.path.clone(),
+            error: elem.error.clone(),
             number: self.counter + 1,
             out_of: self.pic_list.len(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::runtime::{Builder, Runtime};
+
+    /// Buffer of size over n missing files (loading them only sets their path), with its runtime
+    fn buffer(n: usize, size: usize) -> (Runtime, CircularBuffer) {
+        let runtime = Builder::new_current_thread().build().unwrap();
+        let pic_list = (0..n)
+            .map(|i| PathBuf::from(format!("{}.jpg", i)))
+            .collect();
+        let buffer = CircularBuffer::new(pic_list, size).unwrap();
+        (runtime, buffer)
+    }
+
+    /// Checks that the current element is at index and that every loaded element is the right one
+    async fn check(buffer: &CircularBuffer, index: usize) {
+        // letting the spawned loads run
+        tokio::task::yield_now().await;
+        assert_eq!(buffer.counter, index);
+        assert_eq!(buffer.true_size, buffer.indices.len());
+        assert_eq!(buffer.true_size, buffer.back_file + buffer.front_file + 1);
+        assert!(buffer.true_size <= buffer.pic_list.len());
+        assert!(buffer.counter + buffer.front_file < buffer.pic_list.len());
+        for i in buffer.counter - buffer.back_file..=buffer.counter + buffer.front_file {
+            let elem = buffer.buffer[buffer.buffer_idx_of(i)].lock().await;
+            assert_eq!(elem.path, buffer.pic_list[i], "element {}", i);
+        }
+        let stat = buffer.get_elem().await;
+        assert_eq!(stat.path, buffer.pic_list[index]);
+        assert_eq!(stat.number, index + 1);
+        assert_eq!(stat.out_of, buffer.pic_list.len());
+    }
+
+    #[test]
+    fn browse_both_ways() {
+        for size in [1, MIN_BUFFER_SIZE, BUFFER_SIZE, 11] {
+            let (runtime, mut buffer) = buffer(20, size);
+            runtime.block_on(async {
+                buffer.init().await;
+                check(&buffer, 0).await;
+                assert!(!buffer.prev_img().await);
+                for i in 1..20 {
+                    assert!(buffer.next_img().await);
+                    check(&buffer, i).await;
+                }
+                assert!(!buffer.next_img().await);
+                for i in (0..19).rev() {
+                    assert!(buffer.prev_img().await);
+                    check(&buffer, i).await;
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn push_to_small_and_full_buffers() {
+        let (runtime, mut small) = buffer(2, BUFFER_SIZE);
+        runtime.block_on(async {
+            small.init().await;
+            small.push(PathBuf::from("new.jpg")).await;
+            check(&small, 0).await;
+            assert_eq!(small.true_size, 3);
+            small.go_to_last().await;
+            check(&small, 2).await;
+        });
+        let (runtime, mut full) = buffer(10, BUFFER_SIZE);
+        runtime.block_on(async {
+            full.init().await;
+            full.go_to_last().await;
+            full.push(PathBuf::from("new.jpg")).await;
+            check(&full, 9).await;
+            assert!(full.next_img().await);
+            check(&full, 10).await;
+        });
+    }
+
+    #[test]
+    fn remove_at_start_end_and_current() {
+        let (runtime, mut buffer) = buffer(10, BUFFER_SIZE);
+        runtime.block_on(async {
+            buffer.init().await;
+            buffer.go_to(1).await;
+            assert!(buffer.remove(0).await);
+            check(&buffer, 0).await;
+            assert!(buffer.remove(8).await);
+            check(&buffer, 0).await;
+            buffer.go_to_last().await;
+            check(&buffer, 7).await;
+            assert!(buffer.remove(7).await);
+            check(&buffer, 6).await;
+            assert_eq!(buffer.pic_list.len(), 7);
+        });
+    }
+
+    #[test]
+    fn remove_down_to_single_element() {
+        let (runtime, mut buffer) = buffer(3, BUFFER_SIZE);
+        runtime.block_on(async {
+            buffer.init().await;
+            buffer.go_to(1).await;
+            assert!(buffer.remove(2).await);
+            check(&buffer, 1).await;
+            assert!(buffer.remove(0).await);
+            check(&buffer, 0).await;
+            assert!(!buffer.next_img().await);
+            assert!(!buffer.remove(0).await);
+        });
+    }
+
+    #[test]
+    fn delete_first_last_and_middle() {
+        let (runtime, mut buffer) = buffer(12, BUFFER_SIZE);
+        runtime.block_on(async {
+            buffer.init().await;
+            assert!(buffer.delete().await);
+            check(&buffer, 0).await;
+            assert_eq!(buffer.pic_list[0], PathBuf::from("1.jpg"));
+            buffer.go_to(5).await;
+            assert!(buffer.delete().await);
+            check(&buffer, 5).await;
+            assert_eq!(buffer.pic_list[5], PathBuf::from("7.jpg"));
+            buffer.go_to_last().await;
+            assert!(buffer.delete().await);
+            check(&buffer, 8).await;
+            // fewer elements than the buffer holds
+            for _ in 0..4 {
+                assert!(buffer.delete().await);
+            }
+            check(&buffer, 4).await;
+            for left in (1..5).rev() {
+                assert!(buffer.delete().await);
+                check(&buffer, left - 1).await;
+            }
+            assert!(!buffer.delete().await);
+        });
+    }
+
+    #[test]
+    fn go_to_outside_of_the_buffer() {
+        let (runtime, mut buffer) = buffer(30, BUFFER_SIZE);
+        runtime.block_on(async {
+            buffer.init().await;
+            buffer.go_to(20).await;
+            check(&buffer, 20).await;
+            buffer.go_to(0).await;
+            check(&buffer, 0).await;
+            buffer.go_to_last().await;
+            check(&buffer, 29).await;
+            assert!(buffer.prev_img().await);
+            check(&buffer, 28).await;
+        });
+    }
+
+    #[test]
+    fn reorder_keeps_loads_right() {
+        let (runtime, mut buffer) = buffer(30, 6);
+        runtime.block_on(async {
+            buffer.init().await;
+            buffer.go_to(12).await;
+            let reversed: Vec<PathBuf> = buffer.pic_list.iter().rev().cloned().collect();
+            buffer.reorder(reversed, 0).await;
+            check(&buffer, 0).await;
+            assert_eq!(buffer.get_elem().await.path, PathBuf::from("29.jpg"));
+            for i in 1..30 {
+                assert!(buffer.next_img().await);
+                check(&buffer, i).await;
+            }
+            let ordered = (0..30)
+                .map(|i| PathBuf::from(format!("{}.jpg", i)))
+                .collect();
+            buffer.reorder(ordered, 0).await;
+            check(&buffer, 0).await;
+        });
+    }
+
+    /// 16x8 JPEG, white on its left half and black on its right half
+    fn jpeg() -> Vec<u8> {
+        let img = turbojpeg::image::RgbImage::from_fn(16, 8, |x, _| match x < 8 {
+            true => Rgb([255, 255, 255]),
+            false => Rgb([0, 0, 0]),
+        });
+        turbojpeg::compress_image(&img, 95, turbojpeg::Subsamp::None)
+            .unwrap()
+            .to_vec()
+    }
+
+    #[test]
+    fn sync_with_the_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let pic_list: Vec<PathBuf> = (0..10)
+            .map(|i| dir.path().join(format!("{}.jpg", i)))
+            .collect();
+        for path in pic_list.iter() {
+            fs::write(path, "a").unwrap();
+        }
+        let runtime = Builder::new_current_thread().build().unwrap();
+        let mut buffer = CircularBuffer::new(pic_list.clone(), BUFFER_SIZE).unwrap();
+        runtime.block_on(async {
+            buffer.init().await;
+            // loads not run yet are not queued twice
+            assert!(buffer.sync().await);
+            for i in 1..buffer.true_size {
+                assert_eq!(
+                    buffer.loading[buffer.buffer_idx_of(i)].load(Ordering::Acquire),
+                    1
+                );
+            }
+            tokio::task::yield_now().await;
+            fs::remove_file(&pic_list[0]).unwrap();
+            fs::remove_file(&pic_list[1]).unwrap();
+            assert!(buffer.sync_current().await);
+            check(&buffer, 0).await;
+            assert_eq!(buffer.pic_list[0], pic_list[2]);
+            fs::write(&pic_list[3], "changed").unwrap();
+            assert!(buffer.sync().await);
+            tokio::task::yield_now().await;
+            let elem = buffer.buffer[buffer.buffer_idx_of(1)].lock().await;
+            assert_eq!(elem.stamp.map(|stamp| stamp.size), Some(7));
+            drop(elem);
+            for path in pic_list[2..].iter() {
+                fs::remove_file(path).unwrap();
+            }
+            assert!(!buffer.sync().await);
+        });
+    }
+
+    #[test]
+    fn eight_orientations() {
+        let data = jpeg();
+        for orientation in 0..=9 {
+            let decoded = match jpeg_transform(orientation) {
+                Some(transform) => {
+                    let transformed = turbojpeg::transform(&transform, &data).unwrap();
+                    turbojpeg::decompress_image::<Rgb<u8>>(&transformed).unwrap()
+                }
+                None => turbojpeg::decompress_image::<Rgb<u8>>(&data).unwrap(),
+            };
+            let size = match orientation {
+                5..=8 => (8, 16),
+                _ => (16, 8),
+            };
+            assert_eq!(decoded.dimensions(), size, "orientation {}", orientation);
+            // the white half ends up at the top left unless mirrored or turned away
+            let white = decoded.get_pixel(0, 0)[0] > 128;
+            let expected = !matches!(orientation, 2 | 3 | 7 | 8);
+            assert_eq!(white, expected, "orientation {}", orientation);
+        }
+    }
+
+    #[test]
+    fn load_errors_are_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut elem = ImageElement::default();
+        elem.load(dir.path().join("missing.jpg"));
+        assert!(matches!(elem.error, Some(PhogError::Read(_))));
+        assert_eq!(elem.file_name, "missing.jpg");
+        let corrupted = dir.path().join("corrupted.jpg");
+        fs::write(&corrupted, "not a jpeg").unwrap();
+        elem.load(corrupted.clone());
+        assert!(matches!(elem.error, Some(PhogError::Decode(_))));
+        assert_eq!(elem.path, corrupted);
+        let valid = dir.path().join("valid.jpg");
+        fs::write(&valid, jpeg()).unwrap();
+        elem.load(valid);
+        assert!(elem.error.is_none());
+        assert_eq!((elem.raw_img.width(), elem.raw_img.height()), (16, 8));
+        assert!(matches!(
+            CircularBuffer::new(Vec::new(), BUFFER_SIZE),
+            Err(PhogError::EmptyFolder)
+        ));
+    }
+}
@@ -0,0 +1,350 @@
+use crate::bucket::Bucket;
+use crate::circ_buf::MIN_BUFFER_SIZE;
+use crate::error::PhogError;
+use crate::keymap::{KeyBindings, Keymap};
+use crate::logic::{ConflictPolicy, DeleteMode, SortOrder};
+use crate::template::FolderTemplate;
+use crate::transfer::LinkMode;
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Configuration of a folder, overriding the user one
+pub const FOLDER_CONFIG_NAME: &str = ".phog.toml";
+
+/// Colors of the app
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    /// Same as the desktop
+    System,
+    Light,
+    Dark,
+}
+
+/// Time between two steps, written like `4s`, `1.5s`, `500ms` or `1m` (seconds by default)
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Interval(pub Duration);
+
+impl FromStr for Interval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (value, unit) = match s.find(|c: char| c.is_ascii_alphabetic()) {
+            Some(start) => s.split_at(start),
+            None => (s, "s"),
+        };
+        let value: f64 = value
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid interval '{}' (e.g. 4s or 500ms)", s))?;
+        let seconds = match unit {
+            "ms" => value / 1000.0,
+            "s" => value,
+            "m" | "min" => value * 60.0,
+            _ => return Err(format!("unknown unit '{}' in '{}' (ms, s or m)", unit, s)),
+        };
+        match Duration::try_from_secs_f64(seconds) {
+            Ok(duration) if !duration.is_zero() => Ok(Self(duration)),
+            _ => Err(format!("interval '{}' should be more than 0", s)),
+        }
+    }
+}
+
+impl TryFrom<String> for Interval {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Settings from the configuration files (or the command line), unset ones keep their default.
+/// Names are the same as the command line flags
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    pub edit_folder: Option<FolderTemplate>,
+    pub bin_folder: Option<FolderTemplate>,
+    pub edit_with: Option<LinkMode>,
+    pub checksum: Option<bool>,
+    pub on_conflict: Option<ConflictPolicy>,
+    pub delete_to: Option<DeleteMode>,
+    pub buckets: Option<Vec<Bucket>>,
+    pub raw_extensions: Option<Vec<String>>,
+    pub buffer_size: Option<usize>,
+    pub sort: Option<SortOrder>,
+    pub delay_ms: Option<u64>,
+    pub theme: Option<Theme>,
+    /// decisions are recorded and applied after a review
+    pub defer: Option<bool>,
+    /// time each photo is shown in a slideshow
+    pub slideshow_interval: Option<Interval>,
+    /// slideshows start again from the first photo after the last one
+    pub slideshow_loop: Option<bool>,
+    /// slideshows show the photos in random order
+    pub slideshow_shuffle: Option<bool>,
+    /// photos are shown fullscreen on a black background, without the caption
+    pub fullscreen: Option<bool>,
+    /// keys of the actions, `[keys]` table
+    pub keys: Option<KeyBindings>,
+    /// edit and bin folders changed in the app are written back to the configuration
+    pub save_changes: Option<bool>,
+}
+
+impl Config {
+    /// Reads the user configuration, then the one of folder which takes precedence
+    pub fn load(folder: &Path) -> Result<Self, PhogError> {
+        Self::load_from(user_config_path(), folder)
+    }
+
+    /// Reads the user configuration at user (if any), then the one of folder
+    fn load_from(user: Option<PathBuf>, folder: &Path) -> Result<Self, PhogError> {
+        let mut config = Self::default();
+        // last file setting keys, blamed for keys bound to several actions
+        let mut keys_path = None;
+        for path in [user, Some(folder.join(FOLDER_CONFIG_NAME))]
+            .into_iter()
+            .flatten()
+        {
+            if let Some(layer) = Self::read(&path)? {
+                if layer.keys.is_some() {
+                    keys_path = Some(path);
+                }
+                config = layer.or(config);
+            }
+        }
+        if let (Some(keys), Some(path)) = (&config.keys, keys_path) {
+            Keymap::new(keys, &[]).map_err(|err| PhogError::Config(path, err))?;
+        }
+        Ok(config)
+    }
+
+    /// Reads a configuration file, None if there is none
+    fn read(path: &Path) -> Result<Option<Self>, PhogError> {
+        let error = |err: String| PhogError::Config(path.to_path_buf(), err);
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(error(err.to_string())),
+        };
+        let config: Self = toml::from_str(&content).map_err(|err| error(err.to_string()))?;
+        config.validate().map_err(error)?;
+        Ok(Some(config))
+    }
+
+    /// Checks settings that can't be checked while parsing
+    pub fn validate(&self) -> Result<(), String> {
+        if self.buffer_size.is_some_and(|size| size < MIN_BUFFER_SIZE) {
+            return Err(format!(
+                "buffer-size should be at least {}",
+                MIN_BUFFER_SIZE
+            ));
+        }
+        if self.raw_extensions.as_ref().is_some_and(Vec::is_empty) {
+            return Err("raw-extensions is empty".into());
+        }
+        if let Some(keys) = &self.keys {
+            keys.check()?;
+        }
+        Ok(())
+    }
+
+    /// Settings of self, unset ones taken from base
+    pub fn or(self, base: Self) -> Self {
+        Self {
+            edit_folder: self.edit_folder.or(base.edit_folder),
+            bin_folder: self.bin_folder.or(base.bin_folder),
+            edit_with: self.edit_with.or(base.edit_with),
+            checksum: self.checksum.or(base.checksum),
+            on_conflict: self.on_conflict.or(base.on_conflict),
+            delete_to: self.delete_to.or(base.delete_to),
+            buckets: self.buckets.or(base.buckets),
+            raw_extensions: self.raw_extensions.or(base.raw_extensions),
+            buffer_size: self.buffer_size.or(base.buffer_size),
+            sort: self.sort.or(base.sort),
+            delay_ms: self.delay_ms.or(base.delay_ms),
+            theme: self.theme.or(base.theme),
+            defer: self.defer.or(base.defer),
+            slideshow_interval: self.slideshow_interval.or(base.slideshow_interval),
+            slideshow_loop: self.slideshow_loop.or(base.slideshow_loop),
+            slideshow_shuffle: self.slideshow_shuffle.or(base.slideshow_shuffle),
+            fullscreen: self.fullscreen.or(base.fullscreen),
+            keys: match (self.keys, base.keys) {
+                (Some(keys), Some(base_keys)) => Some(keys.or(base_keys)),
+                (keys, base_keys) => keys.or(base_keys),
+            },
+            save_changes: self.save_changes.or(base.save_changes),
+        }
+    }
+
+    /// Writes a setting to the configuration of folder if it has one, else to the user one.
+    /// The rest of the file is kept as is, comments included
+    pub fn save(folder: &Path, key: &str, value: &str) -> Result<(), PhogError> {
+        let folder_config = folder.join(FOLDER_CONFIG_NAME);
+        let path = if folder_config.exists() {
+            folder_config
+        } else {
+            user_config_path().ok_or(PhogError::Config(
+                PathBuf::from("~"),
+                "no configuration folder found".into(),
+            ))?
+        };
+        save_to(&path, key, value)
+    }
+
+    /// Writes a setting to the user configuration, for the ones not tied to a folder
+    pub fn save_user(key: &str, value: impl Into<toml_edit::Value>) -> Result<(), PhogError> {
+        let path = user_config_path().ok_or(PhogError::Config(
+            PathBuf::from("~"),
+            "no configuration folder found".into(),
+        ))?;
+        save_to(&path, key, value)
+    }
+}
+
+/// Writes a setting to the configuration file at path, keeping the rest of it
+fn save_to(path: &Path, key: &str, value: impl Into<toml_edit::Value>) -> Result<(), PhogError> {
+    let error = |err: String| PhogError::Config(path.to_path_buf(), err);
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(error(err.to_string())),
+    };
+    let mut document: toml_edit::DocumentMut = content
+        .parse()
+        .map_err(|err: toml_edit::TomlError| error(err.to_string()))?;
+    document[key] = toml_edit::value(value);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|err| error(err.to_string()))?;
+    }
+    fs::write(path, document.to_string()).map_err(|err| error(err.to_string()))
+}
+
+/// `$XDG_CONFIG_HOME/phog/config.toml`, `$XDG_CONFIG_HOME` defaulting to `~/.config`
+pub fn user_config_path() -> Option<PathBuf> {
+    let config_home = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_home.join("phog").join("config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_intervals() {
+        let interval = |s: &str| s.parse::<Interval>().map(|interval| interval.0);
+        assert_eq!(interval("4s"), Ok(Duration::from_secs(4)));
+        assert_eq!(interval(" 1.5 s "), Ok(Duration::from_millis(1500)));
+        assert_eq!(interval("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(interval("2"), Ok(Duration::from_secs(2)));
+        assert_eq!(interval("1m"), Ok(Duration::from_secs(60)));
+        assert_eq!(interval("1min"), Ok(Duration::from_secs(60)));
+        for invalid in ["", "s", "0s", "-1s", "4h", "fast", "1e400"] {
+            assert!(interval(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn folder_overrides_user() {
+        let home = tempfile::tempdir().unwrap();
+        let folder = tempfile::tempdir().unwrap();
+        let user_config = home.path().join("phog/config.toml");
+        fs::create_dir_all(user_config.parent().unwrap()).unwrap();
+        fs::write(
+            &user_config,
+            "edit-folder = \"~/Pictures/{year}\"\nsort = \"modified\"\nbuffer-size = 12\n",
+        )
+        .unwrap();
+        let folder_config = folder.path().join(FOLDER_CONFIG_NAME);
+        fs::write(
+            &folder_config,
+            "sort = \"name\"\nbuckets = [\"client:c:copy\"]\n",
+        )
+        .unwrap();
+        let config = Config::load_from(Some(user_config.clone()), folder.path()).unwrap();
+        assert_eq!(config.edit_folder.unwrap().to_string(), "~/Pictures/{year}");
+        assert_eq!(config.sort, Some(SortOrder::Name));
+        assert_eq!(config.buffer_size, Some(12));
+        assert_eq!(config.buckets.unwrap()[0].name, "client");
+        assert!(config.theme.is_none());
+
+        // no configuration at all
+        let empty = tempfile::tempdir().unwrap();
+        let config = Config::load_from(None, empty.path()).unwrap();
+        assert!(config.edit_folder.is_none() && config.sort.is_none());
+    }
+
+    #[test]
+    fn keys_checked_once_merged() {
+        let home = tempfile::tempdir().unwrap();
+        let folder = tempfile::tempdir().unwrap();
+        let user_config = home.path().join("config.toml");
+        fs::write(&user_config, "sort = \"modified\"\n[keys]\nedit = \"c\"\n").unwrap();
+        let folder_config = folder.path().join(FOLDER_CONFIG_NAME);
+        fs::write(&folder_config, "sort = \"name\"\n[keys]\nnext = \"e\"\n").unwrap();
+        let config = Config::load_from(Some(user_config.clone()), folder.path()).unwrap();
+        assert_eq!(config.sort, Some(SortOrder::Name));
+        let keys = config.keys.unwrap();
+        assert!(keys.edit.is_some() && keys.next.is_some());
+
+        // edit keeps its default key without the user configuration
+        match Config::load_from(None, folder.path()) {
+            Err(PhogError::Config(path, err)) => {
+                assert_eq!(path, folder_config);
+                assert!(err.contains("more than one action"));
+            }
+            _ => panic!("conflict not found"),
+        }
+    }
+
+    #[test]
+    fn invalid_settings() {
+        let folder = tempfile::tempdir().unwrap();
+        let folder_config = folder.path().join(FOLDER_CONFIG_NAME);
+        for invalid in [
+            "buffer-size = 2",
+            "raw-extensions = []",
+            "edit-folder = \"{week}\"",
+            "buckets = [\"edit:c:copy\"]",
+            "[keys]\nhelp = \"hyper+h\"",
+            "sort = \"random\"",
+            "unknown = true",
+        ] {
+            fs::write(&folder_config, invalid).unwrap();
+            match Config::load_from(None, folder.path()) {
+                Err(PhogError::Config(path, _)) => assert_eq!(path, folder_config),
+                _ => panic!("{} accepted", invalid),
+            }
+        }
+    }
+
+    #[test]
+    fn save_keeps_the_rest_of_the_file() {
+        let folder = tempfile::tempdir().unwrap();
+        let path = folder.path().join(FOLDER_CONFIG_NAME);
+        fs::write(
+            &path,
+            "# my settings\nedit-folder = \"edit\"\nsort = \"name\"\n",
+        )
+        .unwrap();
+        save_to(&path, "edit-folder", "picked").unwrap();
+        save_to(&path, "bin-folder", "~/trash").unwrap();
+        save_to(&path, "fullscreen", true).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.starts_with("# my settings\n"));
+        let config = Config::read(&path).unwrap().unwrap();
+        assert_eq!(config.edit_folder.unwrap().to_string(), "picked");
+        assert_eq!(config.bin_folder.unwrap().to_string(), "~/trash");
+        assert_eq!(config.sort, Some(SortOrder::Name));
+        assert_eq!(config.fullscreen, Some(true));
+    }
+}
@@ -0,0 +1,76 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Operations on the disk a dry run would have done, in order, instead of doing them
+#[derive(Debug, Default)]
+pub struct DryRun {
+    operations: RefCell<Vec<String>>,
+    /// files the operations would have created, with the existing file they would have the
+    /// content of, and files they would have removed (None)
+    files: RefCell<HashMap<PathBuf, Option<PathBuf>>>,
+}
+
+impl DryRun {
+    /// Records an operation on a file, e.g. `copy`, with its destination if any and its size.
+    /// `move` and `rename` take the file away, operations without a destination remove it
+    pub fn record(&self, operation: &str, file: &Path, dest: Option<&Path>) {
+        let content = self.content_of(file).unwrap_or_else(|| file.to_path_buf());
+        let mut files = self.files.borrow_mut();
+        if let Some(dest) = dest {
+            files.insert(dest.to_path_buf(), Some(content));
+        }
+        if dest.is_none() || matches!(operation, "move" | "rename") {
+            files.insert(file.to_path_buf(), None);
+        }
+        drop(files);
+        let size = fs::metadata(file).map_or(0, |metadata| metadata.len());
+        let line = match dest {
+            Some(dest) => format!(
+                "{} {} → {} ({} B)",
+                operation,
+                file.display(),
+                dest.display(),
+                size
+            ),
+            None => format!("{} {} ({} B)", operation, file.display(), size),
+        };
+        self.push(line);
+    }
+
+    /// Records the creation of a folder, once
+    pub fn record_folder(&self, folder: &Path) {
+        let line = format!("create {}", folder.display());
+        if !self.operations.borrow().contains(&line) {
+            self.push(line);
+        }
+    }
+
+    /// Wether the operations would have left a file, None if they did not touch it
+    pub fn exists(&self, path: &Path) -> Option<bool> {
+        self.files.borrow().get(path).map(Option::is_some)
+    }
+
+    /// Existing file with the content a file created by the operations would have
+    pub fn content_of(&self, path: &Path) -> Option<PathBuf> {
+        self.files.borrow().get(path).cloned().flatten()
+    }
+
+    fn push(&self, line: String) {
+        println!("[dry run] {}", line);
+        self.operations.borrow_mut().push(line);
+    }
+
+    pub fn operations(&self) -> Vec<String> {
+        self.operations.borrow().clone()
+    }
+
+    /// Writes the operations to a file, one per line
+    pub fn export(&self, path: &Path) -> io::Result<()> {
+        let mut content = self.operations.borrow().join("\n");
+        content.push('\n');
+        fs::write(path, content)
+    }
+}
@@ -0,0 +1,66 @@
+use std::fmt;
+use std::path::PathBuf;
+
+/// Errors that can happen while scanning a folder or loading an image
+#[derive(Debug, Clone)]
+pub enum PhogError {
+    /// The folder could not be listed
+    FolderScan(PathBuf, String),
+    /// No picture was found in the folder
+    EmptyFolder,
+    /// The file could not be opened or read (deleted, permissions...)
+    Read(String),
+    /// The file was read but is not a valid image (truncated, corrupted...)
+    Decode(String),
+    /// A configuration file could not be read or has invalid settings
+    Config(PathBuf, String),
+    /// The saved decisions could not be read
+    Session(PathBuf, String),
+    /// A list of chosen photos could not be read
+    PickList(PathBuf, String),
+}
+
+impl fmt::Display for PhogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PhogError::FolderScan(path, err) => {
+                write!(f, "Could not scan folder {}: {}", path.display(), err)
+            }
+            PhogError::EmptyFolder => write!(f, "No photos found"),
+            PhogError::Read(err) => write!(f, "Could not read file: {}", err),
+            PhogError::Decode(err) => write!(f, "Could not decode image: {}", err),
+            PhogError::Config(path, err) => {
+                write!(f, "Invalid configuration {}: {}", path.display(), err)
+            }
+            PhogError::Session(path, err) => {
+                write!(f, "Could not read session {}: {}", path.display(), err)
+            }
+            PhogError::PickList(path, err) => {
+                write!(f, "Could not read list {}: {}", path.display(), err)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PhogError {}
+
+impl From<std::io::Error> for PhogError {
+    fn from(err: std::io::Error) -> Self {
+        PhogError::Read(err.to_string())
+    }
+}
+
+impl From<turbojpeg::Error> for PhogError {
+    fn from(err: turbojpeg::Error) -> Self {
+        PhogError::Decode(err.to_string())
+    }
+}
+
+impl From<image::ImageError> for PhogError {
+    fn from(err: image::ImageError) -> Self {
+        match err {
+            image::ImageError::IoError(err) => err.into(),
+            err => PhogError::Decode(err.to_string()),
+        }
+    }
+}
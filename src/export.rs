@@ -0,0 +1,188 @@
+use crate::logic::Sorter;
+use crate::scan::{Scan, Shot};
+use crate::session::Session;
+use std::io::{self, Write};
+
+/// Format of an exported list of photos
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportFormat {
+    /// One file per line (photo, RAW and sidecars), e.g. for rsync --files-from
+    Txt,
+    /// One photo per line with its RAW, sidecars, rating and label
+    Csv,
+    /// List of photos with their RAW, sidecars, rating and label
+    Json,
+}
+
+/// Which photos are exported, all the filters set have to match
+#[derive(Debug, Clone, Default)]
+pub struct Selection {
+    /// copied to the folder of the bucket with this name (`edit` included), or marked for it
+    pub copied_to: Option<String>,
+    /// at least this many stars
+    pub min_rating: Option<i32>,
+    /// color label, in any case
+    pub label: Option<String>,
+}
+
+impl Selection {
+    /// Wether a shot is selected, the decisions of the session counting as done
+    pub fn matches(&self, sorter: &Sorter, session: &Session, shot: &Shot) -> bool {
+        let picture = sorter.get_folder().join(&shot.picture);
+        let copied = self.copied_to.as_ref().is_none_or(|destination| {
+            session
+                .buckets(&sorter.session_file(&picture))
+                .contains(destination)
+                || sorter.copy_in(destination, &picture).is_some()
+        });
+        let rated = self
+            .min_rating
+            .is_none_or(|min| shot.rating.is_some_and(|rating| rating >= min));
+        let labeled = self.label.as_ref().is_none_or(|label| {
+            shot.label
+                .as_ref()
+                .is_some_and(|shot_label| shot_label.eq_ignore_ascii_case(label))
+        });
+        copied && rated && labeled
+    }
+
+    /// Shots of a scan in the selection, in browsing order
+    pub fn select<'a>(&self, scan: &'a Scan, sorter: &Sorter, session: &Session) -> Vec<&'a Shot> {
+        scan.shots
+            .iter()
+            .filter(|shot| self.matches(sorter, session, shot))
+            .collect()
+    }
+}
+
+/// Writes shots in a format, with their names as they are
+pub fn write(shots: &[Shot], format: ExportFormat, out: &mut impl Write) -> io::Result<()> {
+    match format {
+        ExportFormat::Txt => {
+            for shot in shots {
+                writeln!(out, "{}", shot.picture)?;
+                for file in shot.raw.iter().chain(shot.sidecars.iter()) {
+                    writeln!(out, "{}", file)?;
+                }
+            }
+        }
+        ExportFormat::Csv => {
+            writeln!(out, "photo,raw,sidecars,rating,label")?;
+            for shot in shots {
+                let fields = [
+                    shot.picture.clone(),
+                    shot.raw.clone().unwrap_or_default(),
+                    shot.sidecars.join(";"),
+                    shot.rating
+                        .map(|rating| rating.to_string())
+                        .unwrap_or_default(),
+                    shot.label.clone().unwrap_or_default(),
+                ];
+                let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+                writeln!(out, "{}", fields.join(","))?;
+            }
+        }
+        ExportFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, shots)?;
+            writeln!(out)?;
+        }
+    }
+    Ok(())
+}
+
+/// Quotes a CSV field if needed
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::tests::sorter;
+    use std::fs;
+    use std::path::Path;
+
+    fn shot(picture: &str, rating: Option<i32>, label: Option<&str>) -> Shot {
+        Shot {
+            picture: picture.into(),
+            raw: None,
+            sidecars: Vec::new(),
+            rating,
+            label: label.map(Into::into),
+        }
+    }
+
+    #[test]
+    fn csv_fields_are_quoted() {
+        let mut quoted = shot("a, b.jpg", Some(5), Some("Red"));
+        quoted.raw = Some("a \"1\".RAF".into());
+        quoted.sidecars = vec!["a.xmp".into(), "a.pp3".into()];
+        let mut out = Vec::new();
+        write(
+            &[quoted, shot("c.jpg", None, None)],
+            ExportFormat::Csv,
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "photo,raw,sidecars,rating,label\n\
+             \"a, b.jpg\",\"a \"\"1\"\".RAF\",a.xmp;a.pp3,5,Red\n\
+             c.jpg,,,,\n"
+        );
+    }
+
+    #[test]
+    fn selection_filters() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a.jpg", "b.jpg"] {
+            fs::write(dir.path().join(name), name).unwrap();
+        }
+        fs::create_dir(dir.path().join("edit")).unwrap();
+        fs::write(dir.path().join("edit/a.jpg"), "a.jpg").unwrap();
+        let sorter = sorter(dir.path());
+        let mut session = Session::default();
+        session.toggle(Path::new("b.jpg"), "bin");
+        let (a, b) = (
+            shot("a.jpg", Some(3), Some("Red")),
+            shot("b.jpg", None, None),
+        );
+        let matches = |selection: &Selection, shot| selection.matches(&sorter, &session, shot);
+
+        assert!(matches(&Selection::default(), &b));
+        let edited = Selection {
+            copied_to: Some("edit".into()),
+            ..Default::default()
+        };
+        assert!(matches(&edited, &a));
+        assert!(!matches(&edited, &b));
+        // marked for the bin but not there yet
+        let binned = Selection {
+            copied_to: Some("bin".into()),
+            ..Default::default()
+        };
+        assert!(!matches(&binned, &a));
+        assert!(matches(&binned, &b));
+        let rated = |min_rating| Selection {
+            min_rating: Some(min_rating),
+            ..Default::default()
+        };
+        assert!(matches(&rated(3), &a));
+        assert!(!matches(&rated(4), &a));
+        assert!(!matches(&rated(0), &b));
+        let red = Selection {
+            label: Some("red".into()),
+            ..edited.clone()
+        };
+        assert!(matches(&red, &a));
+        let green = Selection {
+            label: Some("Green".into()),
+            ..edited
+        };
+        assert!(!matches(&green, &a));
+    }
+}
@@ -0,0 +1,226 @@
+use crate::checksum::{self, MANIFEST_NAME};
+use crate::error::PhogError;
+use crate::logic::{is_picture, with_suffix, Sorter};
+use crate::template::FolderTemplate;
+use crate::transfer;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Folder of the library pictures are copied to, from the date they were taken
+pub const LIBRARY_FOLDER: &str = "{year}/{date}";
+
+/// Outcome of copying a card to the library
+#[derive(Debug, Default)]
+pub struct Ingested {
+    /// pictures copied, where they are in the library
+    pub pictures: Vec<PathBuf>,
+    /// files copied, RAW included
+    pub copied: usize,
+    /// files already in the library
+    pub skipped: usize,
+    /// files that could not be copied or whose copy does not match, as `file → folder`
+    pub failed: Vec<String>,
+    /// RAW files of the card without a picture, left there
+    pub orphan_raws: usize,
+}
+
+/// Copies the pictures of a card (all its subfolders) and their RAW to dated folders of the
+/// library, the date being the modification one for pictures without EXIF. Copies are checked
+/// and recorded in the checksum manifest of their folder. A picture and its RAW go together:
+/// skipped when all their contents are already recorded in the library, and copied or removed
+/// together otherwise. The sorter of the card gives the RAW extensions and the dry run
+pub fn ingest(sorter: &Sorter, library: &Path) -> Result<Ingested, PhogError> {
+    let template: FolderTemplate = LIBRARY_FOLDER.parse().expect("valid folder template");
+    let card = sorter.get_folder();
+    let mut files = Vec::new();
+    card_files(card, &mut files)
+        .map_err(|err| PhogError::FolderScan(card.to_path_buf(), err.to_string()))?;
+    files.sort();
+    let mut known = library_hashes(library);
+    let mut outcome = Ingested::default();
+    let mut paired = HashSet::new();
+    for picture in files.iter().filter(|file| is_picture(file)) {
+        let folder = template.resolve_dated(library, picture);
+        let mut shot = vec![picture.clone()];
+        if let Some(raw) = sorter.raw_in(picture, &files) {
+            paired.insert(raw.clone());
+            shot.push(raw);
+        }
+        let failed = |shot: &[PathBuf]| {
+            shot.iter()
+                .map(|file| format!("{} → {}", file.display(), folder.display()))
+                .collect::<Vec<_>>()
+        };
+        // hashed before copying, so the copies are checked against the card
+        let hashes: io::Result<Vec<blake3::Hash>> =
+            shot.iter().map(|file| checksum::hash_file(file)).collect();
+        let Ok(hashes) = hashes else {
+            outcome.failed.extend(failed(&shot));
+            continue;
+        };
+        let dests = destinations(&shot, &folder);
+        let imported = |(hash, dest): (&blake3::Hash, &PathBuf)| {
+            known.contains(hash.to_hex().as_str()) || dest.exists()
+        };
+        if hashes.iter().zip(&dests).all(imported) {
+            outcome.skipped += shot.len();
+            continue;
+        }
+        // files of the shot already at their destination are kept
+        let copies: Vec<(&Path, &Path, &blake3::Hash)> = shot
+            .iter()
+            .zip(&dests)
+            .zip(&hashes)
+            .filter(|((_, dest), _)| !dest.exists())
+            .map(|((file, dest), hash)| (file.as_path(), dest.as_path(), hash))
+            .collect();
+        if import(sorter, &copies).is_err() {
+            outcome.failed.extend(failed(&shot));
+            continue;
+        }
+        known.extend(hashes.iter().map(|hash| hash.to_hex().to_string()));
+        outcome.copied += copies.len();
+        outcome.skipped += shot.len() - copies.len();
+        outcome.pictures.push(dests[0].clone());
+    }
+    outcome.orphan_raws = files
+        .iter()
+        .filter(|file| sorter.is_raw(file) && !paired.contains(*file))
+        .count();
+    Ok(outcome)
+}
+
+/// Files of a card, in all its subfolders except hidden ones
+fn card_files(folder: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(folder)?.flatten() {
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            card_files(&entry.path(), files)?;
+        } else if file_type.is_file() {
+            files.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+/// Hashes recorded in the checksum manifests of the library
+fn library_hashes(library: &Path) -> HashSet<String> {
+    let mut hashes = HashSet::new();
+    let mut folders = vec![library.to_path_buf()];
+    while let Some(folder) = folders.pop() {
+        let Ok(entries) = fs::read_dir(&folder) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                folders.push(entry.path());
+            } else if entry.file_name() == MANIFEST_NAME {
+                let manifest = checksum::read_manifest(&folder).unwrap_or_default();
+                hashes.extend(manifest.into_iter().map(|(hash, _)| hash));
+            }
+        }
+    }
+    hashes
+}
+
+/// Where the files of a shot go in a folder, keeping the same names: with a `_n` suffix
+/// if a different file has the name (cameras restart their numbering), the same name if
+/// the file is already there
+fn destinations(shot: &[PathBuf], folder: &Path) -> Vec<PathBuf> {
+    (0..)
+        .map(|n| {
+            shot.iter()
+                .map(|file| {
+                    let dest = folder.join(file.file_name().unwrap_or_default());
+                    match n {
+                        0 => dest,
+                        n => with_suffix(&dest, n),
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .find(|dests| {
+            shot.iter().zip(dests).all(|(file, dest)| {
+                !dest.exists() || transfer::same_content(file, dest).unwrap_or(false)
+            })
+        })
+        .unwrap_or_default()
+}
+
+/// Copies the files of a shot (file, destination, hash of the file), checks the copies and
+/// records them in the manifest of their folder once they are all there. If one fails, the
+/// others are removed (only logged in a dry run)
+fn import(sorter: &Sorter, copies: &[(&Path, &Path, &blake3::Hash)]) -> io::Result<()> {
+    if let Some(dry_run) = sorter.get_dry_run() {
+        for (file, dest, _) in copies {
+            dry_run.record_folder(dest.parent().unwrap_or(dest));
+            dry_run.record("copy", file, Some(dest));
+        }
+        return Ok(());
+    }
+    let mut copied = Vec::new();
+    for (file, dest, hash) in copies {
+        let checked = fs::create_dir_all(dest.parent().unwrap_or(dest))
+            .and_then(|_| fs::copy(file, dest))
+            .and_then(|_| checksum::matches(dest, hash));
+        copied.push(*dest);
+        let error = match checked {
+            Ok(true) => continue,
+            Ok(false) => {
+                io::Error::new(io::ErrorKind::InvalidData, "copy differs from the original")
+            }
+            Err(err) => err,
+        };
+        for dest in copied {
+            let _ = fs::remove_file(dest);
+        }
+        return Err(error);
+    }
+    for (_, dest, hash) in copies {
+        if let Err(err) = checksum::record(dest, hash) {
+            println!(
+                "Could not record the checksum of {}: {}",
+                dest.display(),
+                err
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::tests::sorter;
+
+    #[test]
+    fn shots_keep_together() {
+        let (card, library) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        fs::create_dir(card.path().join("DCIM")).unwrap();
+        fs::write(card.path().join("DCIM/a.jpg"), "a").unwrap();
+        fs::write(card.path().join("DCIM/a.RAF"), "raf").unwrap();
+        fs::write(card.path().join("b.RAF"), "orphan").unwrap();
+        // no EXIF: dated from the files, written just now
+        let folder = library
+            .path()
+            .join(chrono::Local::now().format("%Y/%Y-%m-%d").to_string());
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("a.jpg"), "another a").unwrap();
+        let ingested = ingest(&sorter(card.path()), library.path()).unwrap();
+        assert_eq!((ingested.copied, ingested.skipped), (2, 0));
+        assert_eq!(ingested.pictures, [folder.join("a_1.jpg")]);
+        assert_eq!(ingested.orphan_raws, 1);
+        assert_eq!(fs::read(folder.join("a_1.RAF")).unwrap(), b"raf");
+        assert!(!folder.join("a.RAF").exists());
+        let again = ingest(&sorter(card.path()), library.path()).unwrap();
+        assert_eq!((again.copied, again.skipped), (0, 2));
+        assert!(again.pictures.is_empty());
+    }
+}
@@ -0,0 +1,415 @@
+use crate::logic::{KeyAction, KeyHelp};
+use serde::Deserialize;
+use slint::platform::Key;
+use std::fmt;
+use std::str::FromStr;
+
+/// Keys that don't write a character: name in the configuration, key and label in the help
+const NAMED_KEYS: [(&str, Key, &str); 27] = [
+    ("left", Key::LeftArrow, "Left"),
+    ("right", Key::RightArrow, "Right"),
+    ("up", Key::UpArrow, "Up"),
+    ("down", Key::DownArrow, "Down"),
+    ("escape", Key::Escape, "Esc"),
+    ("esc", Key::Escape, "Esc"),
+    ("space", Key::Space, "Space"),
+    ("enter", Key::Return, "Enter"),
+    ("tab", Key::Tab, "Tab"),
+    ("backspace", Key::Backspace, "Backspace"),
+    ("delete", Key::Delete, "Delete"),
+    ("home", Key::Home, "Home"),
+    ("end", Key::End, "End"),
+    ("pageup", Key::PageUp, "PageUp"),
+    ("pagedown", Key::PageDown, "PageDown"),
+    ("f1", Key::F1, "F1"),
+    ("f2", Key::F2, "F2"),
+    ("f3", Key::F3, "F3"),
+    ("f4", Key::F4, "F4"),
+    ("f5", Key::F5, "F5"),
+    ("f6", Key::F6, "F6"),
+    ("f7", Key::F7, "F7"),
+    ("f8", Key::F8, "F8"),
+    ("f9", Key::F9, "F9"),
+    ("f10", Key::F10, "F10"),
+    ("f11", Key::F11, "F11"),
+    ("f12", Key::F12, "F12"),
+];
+
+/// A key with its modifiers, written like `e`, `alt+e` or `left` in the configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyPress {
+    /// text of the key event (special character for named keys)
+    pub text: String,
+    pub alt: bool,
+    pub control: bool,
+}
+
+impl FromStr for KeyPress {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (modifiers, key) = match s.rsplit_once('+') {
+            // "+" or "alt++", the key being "+"
+            Some((modifiers, "")) => (modifiers.strip_suffix('+').unwrap_or(modifiers), "+"),
+            Some((modifiers, key)) => (modifiers, key),
+            None => ("", s),
+        };
+        let mut press = Self {
+            text: key.into(),
+            alt: false,
+            control: false,
+        };
+        for modifier in modifiers.split('+').filter(|modifier| !modifier.is_empty()) {
+            match modifier.to_lowercase().as_str() {
+                "alt" => press.alt = true,
+                "ctrl" | "control" => press.control = true,
+                _ => {
+                    return Err(format!(
+                        "unknown modifier '{}' in '{}' (alt or ctrl)",
+                        modifier, s
+                    ))
+                }
+            }
+        }
+        if key.chars().count() == 1 {
+            return Ok(press);
+        }
+        let Some((_, named, _)) = NAMED_KEYS
+            .iter()
+            .find(|(name, _, _)| key.eq_ignore_ascii_case(name))
+        else {
+            return Err(format!("unknown key '{}' in '{}'", key, s));
+        };
+        press.text = char::from(*named).into();
+        Ok(press)
+    }
+}
+
+impl From<&str> for KeyPress {
+    /// Key without modifiers
+    fn from(text: &str) -> Self {
+        Self {
+            text: text.into(),
+            alt: false,
+            control: false,
+        }
+    }
+}
+
+impl fmt::Display for KeyPress {
+    /// Label of the key for the help, e.g. `Alt+e` or `Left`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.control {
+            write!(f, "Ctrl+")?;
+        }
+        if self.alt {
+            write!(f, "Alt+")?;
+        }
+        let named = NAMED_KEYS
+            .iter()
+            .find(|(_, key, _)| self.text.chars().eq([char::from(*key)]));
+        match named {
+            Some((_, _, label)) => write!(f, "{}", label),
+            None => write!(f, "{}", self.text),
+        }
+    }
+}
+
+/// One or several keys for an action
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum KeyList {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeyList {
+    fn parse(&self) -> Result<Vec<KeyPress>, String> {
+        match self {
+            KeyList::One(key) => Ok(vec![key.parse()?]),
+            KeyList::Many(keys) => keys.iter().map(|key| key.parse()).collect(),
+        }
+    }
+}
+
+/// `[keys]` table of the configuration, replacing the default keys of the actions set
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct KeyBindings {
+    pub help: Option<KeyList>,
+    pub prev: Option<KeyList>,
+    pub next: Option<KeyList>,
+    pub edit: Option<KeyList>,
+    pub delete: Option<KeyList>,
+    pub edit_folder: Option<KeyList>,
+    pub bin_folder: Option<KeyList>,
+    pub review: Option<KeyList>,
+    pub clean_raws: Option<KeyList>,
+    pub rename: Option<KeyList>,
+    pub undo_rename: Option<KeyList>,
+    pub slideshow: Option<KeyList>,
+    pub fullscreen: Option<KeyList>,
+    pub quit: Option<KeyList>,
+}
+
+impl KeyBindings {
+    /// Keys set for each action, with its default keys
+    fn table(&self) -> [(KeyAction, &Option<KeyList>, &[&str]); 12] {
+        [
+            (KeyAction::Help, &self.help, &["h"]),
+            (KeyAction::Prev, &self.prev, &["left"]),
+            (KeyAction::Next, &self.next, &["right"]),
+            (KeyAction::EditFolder, &self.edit_folder, &["alt+e"]),
+            (KeyAction::BinFolder, &self.bin_folder, &["alt+d"]),
+            (KeyAction::Review, &self.review, &["enter"]),
+            (KeyAction::CleanRaws, &self.clean_raws, &["alt+r"]),
+            (KeyAction::Rename, &self.rename, &["alt+n"]),
+            (KeyAction::UndoRename, &self.undo_rename, &["alt+u"]),
+            (KeyAction::Slideshow, &self.slideshow, &["f5"]),
+            (KeyAction::Fullscreen, &self.fullscreen, &["f11", "f"]),
+            (KeyAction::Quit, &self.quit, &["escape"]),
+        ]
+    }
+
+    /// Keys set for the built-in buckets, with their default key
+    fn bucket_table(&self) -> [(&str, &Option<KeyList>, &str); 2] {
+        [("edit", &self.edit, "e"), ("delete", &self.delete, "d")]
+    }
+
+    /// Checks the keys set, conflicts between actions being only known once all the
+    /// configurations are merged
+    pub fn check(&self) -> Result<(), String> {
+        for (_, keys, _) in self.table() {
+            if let Some(keys) = keys {
+                keys.parse()?;
+            }
+        }
+        for (name, keys, _) in self.bucket_table() {
+            if let Some(keys) = keys {
+                bucket_key(name, keys)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Keys of self, unset ones taken from base
+    pub fn or(self, base: Self) -> Self {
+        Self {
+            help: self.help.or(base.help),
+            prev: self.prev.or(base.prev),
+            next: self.next.or(base.next),
+            edit: self.edit.or(base.edit),
+            delete: self.delete.or(base.delete),
+            edit_folder: self.edit_folder.or(base.edit_folder),
+            bin_folder: self.bin_folder.or(base.bin_folder),
+            review: self.review.or(base.review),
+            clean_raws: self.clean_raws.or(base.clean_raws),
+            rename: self.rename.or(base.rename),
+            undo_rename: self.undo_rename.or(base.undo_rename),
+            slideshow: self.slideshow.or(base.slideshow),
+            fullscreen: self.fullscreen.or(base.fullscreen),
+            quit: self.quit.or(base.quit),
+        }
+    }
+}
+
+/// Key of a built-in bucket, a single key without modifiers like the keys of the other buckets
+fn bucket_key(name: &str, keys: &KeyList) -> Result<KeyPress, String> {
+    match &keys.parse()?[..] {
+        [press] if !press.alt && !press.control => Ok(press.clone()),
+        _ => Err(format!("{} takes a single key without modifiers", name)),
+    }
+}
+
+/// Actions of the app with the keys triggering them
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: Vec<(KeyAction, Vec<KeyPress>)>,
+    /// keys of the built-in edit and bin buckets
+    edit_key: KeyPress,
+    delete_key: KeyPress,
+}
+
+impl Keymap {
+    /// Default keys replaced by the ones of bindings, a key can only trigger one action.
+    /// Default keys of the actions already set in bindings or taken (by buckets) are left out
+    pub fn new(bindings: &KeyBindings, taken: &[KeyPress]) -> Result<Self, String> {
+        let [edit_key, delete_key] = bindings.bucket_table().map(|(name, keys, default)| {
+            keys.as_ref().map_or_else(
+                || Ok(KeyPress::from(default)),
+                |keys| bucket_key(name, keys),
+            )
+        });
+        let mut keymap = Self {
+            bindings: Vec::new(),
+            edit_key: edit_key?,
+            delete_key: delete_key?,
+        };
+        if keymap.edit_key == keymap.delete_key {
+            return Err(format!(
+                "key '{}' is bound to more than one action",
+                keymap.edit_key
+            ));
+        }
+        // keys set in bindings, whatever the action
+        let set: Vec<Option<Vec<KeyPress>>> = bindings
+            .table()
+            .into_iter()
+            .map(|(_, keys, _)| keys.as_ref().map(KeyList::parse).transpose())
+            .collect::<Result<_, _>>()?;
+        let is_free = |key: &KeyPress| {
+            !taken.contains(key)
+                && *key != keymap.edit_key
+                && *key != keymap.delete_key
+                && !set.iter().flatten().any(|keys| keys.contains(key))
+        };
+        for ((action, _, default), keys) in bindings.table().into_iter().zip(&set) {
+            let keys = match keys {
+                Some(keys) => keys.clone(),
+                None => default
+                    .iter()
+                    .map(|key| key.parse::<KeyPress>())
+                    .filter(|key| key.as_ref().map_or(true, is_free))
+                    .collect::<Result<_, _>>()?,
+            };
+            if let Some(key) = keys.iter().find(|key| keymap.is_used(key)) {
+                return Err(format!("key '{}' is bound to more than one action", key));
+            }
+            keymap.bindings.push((action, keys));
+        }
+        Ok(keymap)
+    }
+
+    /// Action triggered by a key, None if there is none
+    pub fn action(&self, press: &KeyPress) -> KeyAction {
+        self.bindings
+            .iter()
+            .find(|(_, keys)| keys.contains(press))
+            .map_or(KeyAction::None, |(action, _)| *action)
+    }
+
+    /// Wether a key triggers an action or a built-in bucket
+    pub fn is_used(&self, press: &KeyPress) -> bool {
+        self.action(press) != KeyAction::None
+            || *press == self.edit_key
+            || *press == self.delete_key
+    }
+
+    /// Key of the built-in edit bucket
+    pub fn edit_key(&self) -> &str {
+        &self.edit_key.text
+    }
+
+    /// Key of the built-in bin bucket
+    pub fn delete_key(&self) -> &str {
+        &self.delete_key.text
+    }
+
+    /// Keys of each action, for the help
+    pub fn help(&self) -> Vec<KeyHelp> {
+        self.bindings
+            .iter()
+            .filter(|(_, keys)| !keys.is_empty())
+            .map(|(action, keys)| KeyHelp {
+                action: *action,
+                keys: keys
+                    .iter()
+                    .map(KeyPress::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+                    .into(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(s: &str) -> KeyPress {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_key_presses() {
+        assert_eq!(press("e"), KeyPress::from("e"));
+        let alt_e = press("alt+e");
+        assert!(alt_e.alt && !alt_e.control && alt_e.text == "e");
+        let left = press("Ctrl+Alt+LEFT");
+        assert!(left.alt && left.control);
+        assert_eq!(left.text, char::from(Key::LeftArrow).to_string());
+        assert_eq!(press("+").text, "+");
+        let alt_plus = press("alt++");
+        assert!(alt_plus.alt && alt_plus.text == "+");
+        assert!("shift+e".parse::<KeyPress>().is_err());
+        assert!("page".parse::<KeyPress>().is_err());
+    }
+
+    #[test]
+    fn key_labels() {
+        assert_eq!(press("alt+e").to_string(), "Alt+e");
+        assert_eq!(press("ctrl+f11").to_string(), "Ctrl+F11");
+        assert_eq!(press("esc").to_string(), "Esc");
+    }
+
+    #[test]
+    fn bindings_replace_defaults() {
+        let bindings: KeyBindings = toml::from_str(
+            r#"
+            prev = ["left", "q"]
+            edit = "c"
+            "#,
+        )
+        .unwrap();
+        let keymap = Keymap::new(&bindings, &[]).unwrap();
+        assert_eq!(keymap.action(&press("q")), KeyAction::Prev);
+        assert_eq!(keymap.action(&press("left")), KeyAction::Prev);
+        assert_eq!(keymap.edit_key(), "c");
+        assert_eq!(keymap.delete_key(), "d");
+        assert!(keymap.is_used(&press("c")));
+        assert!(!keymap.is_used(&press("e")));
+        assert_eq!(keymap.action(&press("c")), KeyAction::None);
+        let help = keymap.help();
+        let prev = help
+            .iter()
+            .find(|key| key.action == KeyAction::Prev)
+            .unwrap();
+        assert_eq!(prev.keys, "Left, q");
+    }
+
+    #[test]
+    fn conflicts_and_invalid_keys() {
+        let bindings: KeyBindings = toml::from_str(r#"next = "e""#).unwrap();
+        assert!(bindings.check().is_ok());
+        assert!(Keymap::new(&bindings, &[])
+            .unwrap_err()
+            .contains("more than one action"));
+        // the default key of edit given to another action
+        let rebound: KeyBindings = toml::from_str("next = \"e\"\nedit = \"c\"").unwrap();
+        assert!(Keymap::new(&rebound, &[]).is_ok());
+        // default keys of actions set for another one or taken by a bucket are left out
+        let fullscreen = |keymap: &Keymap| {
+            let help = keymap.help();
+            let help = help.iter().find(|key| key.action == KeyAction::Fullscreen);
+            help.map(|help| help.keys.to_string())
+        };
+        let taken: KeyBindings = toml::from_str(r#"prev = "f""#).unwrap();
+        let keymap = Keymap::new(&taken, &[]).unwrap();
+        assert_eq!(keymap.action(&press("f")), KeyAction::Prev);
+        assert_eq!(fullscreen(&keymap).as_deref(), Some("F11"));
+        let keymap = Keymap::new(&KeyBindings::default(), &[press("f")]).unwrap();
+        assert!(!keymap.is_used(&press("f")));
+        let keymap = Keymap::new(&taken, &[press("f11")]).unwrap();
+        assert_eq!(fullscreen(&keymap), None);
+        let invalid: KeyBindings = toml::from_str(r#"help = "hyper+h""#).unwrap();
+        assert!(invalid.check().is_err());
+        for invalid in [r#"edit = "alt+c""#, r#"delete = ["x", "y"]"#] {
+            let bucket_keys: KeyBindings = toml::from_str(invalid).unwrap();
+            assert!(bucket_keys.check().unwrap_err().contains("single key"));
+        }
+        let same: KeyBindings = toml::from_str(r#"delete = "e""#).unwrap();
+        assert!(Keymap::new(&same, &[]).is_err());
+        assert!(toml::from_str::<KeyBindings>(r#"jump = "j""#).is_err());
+    }
+}
@@ -1,2 +1,17 @@
+pub mod bucket;
+pub mod checksum;
 mod circ_buf;
+pub mod config;
+pub mod dry_run;
+pub mod error;
+pub mod export;
+pub mod ingest;
+pub mod keymap;
 pub mod logic;
+pub mod pick_list;
+pub mod rename;
+pub mod scan;
+pub mod session;
+pub mod template;
+pub mod transfer;
+pub mod watch;
@@ -1,72 +1,885 @@
+use crate::bucket::{self, Bucket};
+use crate::checksum;
 use crate::circ_buf::CircularBuffer;
 pub use crate::circ_buf::ImageStat;
+pub use crate::circ_buf::BUFFER_SIZE;
+use crate::dry_run::DryRun;
+use crate::error::PhogError;
+use crate::rename::{RenamePattern, RenamePlan};
+use crate::scan::sidecars_of;
+use crate::session::{Session, SESSION_NAME};
+use crate::template::FolderTemplate;
+use crate::transfer::{self, LinkMode};
+use std::collections::HashSet;
 use std::fs;
-use std::path::PathBuf;
+use std::hash::{BuildHasher, RandomState};
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// Where pictures go when deleted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeleteMode {
+    /// Moved to the bin folder
+    Folder,
+    /// Sent to the desktop trash, where they can be restored from
+    Trash,
+}
+
+/// What to do when a different picture with the same name is already in the destination
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    /// Add a number at the end of the name
+    Rename,
+    /// Replace the picture in the destination
+    Overwrite,
+    /// Leave both pictures where they are
+    Skip,
+}
+
+/// Order in which pictures are browsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    /// By file name
+    Name,
+    /// By last modification, oldest first
+    Modified,
+}
+
+/// Where a picture goes in a folder once name conflicts are solved
+enum Destination {
+    /// the same picture (and RAW) is already there
+    Identical(PathBuf, PathBuf),
+    /// a different picture with the same name is there and is left untouched
+    Skipped(PathBuf),
+    /// destination of the picture and its RAW, with how a conflict was solved
+    Paths(PathBuf, PathBuf, Conflict),
+}
+
+/// Outcome of sending a picture to a bucket, to be displayed
+#[derive(Debug, PartialEq)]
+pub struct Report {
+    pub message: Message,
+    /// how the picture was sent, a link may fall back to a copy
+    pub mode: BucketMode,
+    pub conflict: Conflict,
+    /// name of the picture in the destination
+    pub new_name: String,
+}
+
+impl Report {
+    fn new(message: Message, mode: BucketMode) -> Self {
+        Self {
+            message,
+            mode,
+            conflict: Conflict::None,
+            new_name: String::new(),
+        }
+    }
+
+    /// Adds where the picture is in the destination and how a name conflict there was solved
+    fn at(mut self, conflict: Conflict, dest: &Path) -> Self {
+        self.conflict = conflict;
+        self.new_name = dest
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        self
+    }
+}
+
+impl Message {
+    /// Wether the picture could not be sent
+    fn failed(&self) -> bool {
+        matches!(
+            self,
+            Message::Failed | Message::Skipped | Message::Corrupted
+        )
+    }
+}
+
+/// Sends the pictures of a folder and their RAW to the buckets
+pub struct Sorter {
+    /// browsed folder, relative destinations are inside it
+    folder: PathBuf,
+    /// destinations of the pictures, the built-in edit and bin first
+    buckets: Vec<Bucket>,
+    conflict_policy: ConflictPolicy,
+    /// copies and links are checked against the original and recorded in the checksum
+    /// manifest of their folder
+    verify_copies: bool,
+    /// extensions of the RAW next to a picture, the first one found is used
+    raw_extensions: Vec<String>,
+    /// operations on the disk only logged, None when they are done
+    dry_run: Option<DryRun>,
+}
+
+/// Outcome of applying decisions
+#[derive(Debug, Default)]
+pub struct Applied {
+    /// number of decisions applied
+    pub applied: usize,
+    /// decisions that failed, as `file → destination`
+    pub failed: Vec<String>,
+    /// decisions that failed, to be tried again
+    pub remaining: Session,
+    /// pictures that left the folder
+    pub removed: Vec<PathBuf>,
+}
 
 pub struct AppLogic {
     buffer: CircularBuffer,
-    edit_folder: PathBuf,
-    delete_folder: PathBuf,
-    current_folder: PathBuf,
-    current_name: String,
+    sorter: Sorter,
+    /// decisions to apply later, None when acting right away
+    session: Option<Session>,
+    current_path: PathBuf,
+    /// renames shown to be confirmed
+    rename_plan: Option<RenamePlan>,
+    /// order of the pictures before they were shuffled, None when they are not
+    unshuffled: Option<Vec<PathBuf>>,
 }
 
 slint::include_modules!();
 
-impl AppLogic {
-    pub fn new(folder_path: PathBuf, edit_folder_name: String, delete_folder_name: String) -> Self {
-        let files = fs::read_dir(folder_path.clone()).expect("Folder scan failed");
-        let mut pic_list: Vec<PathBuf> = Vec::new();
+impl Sorter {
+    pub fn new(
+        folder: PathBuf,
+        edit_folder: FolderTemplate,
+        delete_folder: FolderTemplate,
+        buckets: Vec<Bucket>,
+    ) -> Self {
+        let mut all_buckets = vec![Bucket::edit(edit_folder), Bucket::bin(delete_folder)];
+        all_buckets.extend(buckets);
+        Self {
+            folder,
+            buckets: all_buckets,
+            conflict_policy: ConflictPolicy::Rename,
+            verify_copies: false,
+            raw_extensions: vec!["RAF".into()],
+            dry_run: None,
+        }
+    }
+
+    /// Browsed folder
+    pub fn get_folder(&self) -> &Path {
+        &self.folder
+    }
+    pub fn set_edit_folder(&mut self, template: &str) -> Result<(), String> {
+        let template = template.parse()?;
+        if let Some(bucket) = self.bucket_mut(bucket::EDIT) {
+            bucket.folder = template;
+        }
+        Ok(())
+    }
+    pub fn get_edit_folder(&mut self) -> String {
+        self.folder_template(bucket::EDIT)
+    }
+    pub fn set_edit_key(&mut self, key: &str) {
+        if let Some(bucket) = self.bucket_mut(bucket::EDIT) {
+            bucket.key = key.into();
+        }
+    }
+    pub fn set_edit_mode(&mut self, mode: LinkMode) {
+        if let Some(bucket) = self.bucket_mut(bucket::EDIT) {
+            bucket.mode = mode.into();
+        }
+    }
+    pub fn set_delete_key(&mut self, key: &str) {
+        if let Some(bucket) = self.bucket_mut(bucket::BIN) {
+            bucket.key = key.into();
+        }
+    }
+    pub fn set_delete_mode(&mut self, mode: DeleteMode) {
+        if let Some(bucket) = self.bucket_mut(bucket::BIN) {
+            bucket.mode = match mode {
+                DeleteMode::Folder => BucketMode::Move,
+                DeleteMode::Trash => BucketMode::Trash,
+            };
+        }
+    }
+    pub fn set_raw_extensions(&mut self, extensions: Vec<String>) {
+        if !extensions.is_empty() {
+            self.raw_extensions = extensions;
+        }
+    }
+    /// Only logs the operations on the disk instead of doing them
+    pub fn set_dry_run(&mut self) {
+        self.dry_run = Some(DryRun::default());
+    }
+    /// Operations logged since the start, None if not a dry run
+    pub fn get_dry_run(&self) -> Option<&DryRun> {
+        self.dry_run.as_ref()
+    }
+    pub fn set_conflict_policy(&mut self, policy: ConflictPolicy) {
+        self.conflict_policy = policy;
+    }
+    pub fn set_verify_copies(&mut self, verify: bool) {
+        self.verify_copies = verify;
+    }
+    pub fn set_delete_folder(&mut self, template: &str) -> Result<(), String> {
+        let template = template.parse()?;
+        if let Some(bucket) = self.bucket_mut(bucket::BIN) {
+            bucket.folder = template;
+        }
+        Ok(())
+    }
+    pub fn get_delete_folder(&mut self) -> String {
+        self.folder_template(bucket::BIN)
+    }
+
+    fn bucket(&self, name: &str) -> Option<&Bucket> {
+        self.buckets.iter().find(|bucket| bucket.name == name)
+    }
+
+    fn bucket_mut(&mut self, name: &str) -> Option<&mut Bucket> {
+        self.buckets.iter_mut().find(|bucket| bucket.name == name)
+    }
+
+    /// Folder template of a bucket, as given by the user
+    fn folder_template(&self, name: &str) -> String {
+        self.bucket(name)
+            .map(|bucket| bucket.folder.to_string())
+            .unwrap_or_default()
+    }
+
+    pub fn get_buckets(&self) -> &[Bucket] {
+        &self.buckets
+    }
+
+    /// RAW files of the folder whose picture is gone
+    pub fn orphan_raws(&self) -> Result<Vec<PathBuf>, PhogError> {
+        let files: Vec<PathBuf> = fs::read_dir(&self.folder)
+            .map_err(|err| PhogError::FolderScan(self.folder.clone(), err.to_string()))?
+            .flatten()
+            .map(|entry| entry.path())
+            .collect();
+        let paired: Vec<PathBuf> = list_pictures(&self.folder, SortOrder::Name)?
+            .iter()
+            .filter_map(|picture| self.raw_in(picture, &files))
+            .collect();
+        let mut raws: Vec<PathBuf> = files
+            .into_iter()
+            .filter(|path| path.is_file() && self.is_raw(path) && !paired.contains(path))
+            .collect();
+        raws.sort();
+        Ok(raws)
+    }
+
+    /// Sends RAW files to the bin. Returns the ones that could not be
+    pub fn remove_orphan_raws(&self, raws: &[PathBuf]) -> Vec<PathBuf> {
+        raws.iter()
+            .filter(|raw| self.remove_orphan_raw(raw).is_err())
+            .cloned()
+            .collect()
+    }
+
+    fn remove_orphan_raw(&self, raw: &Path) -> io::Result<()> {
+        let Some(bin) = self.bucket(bucket::BIN) else {
+            return Err(io::ErrorKind::NotFound.into());
+        };
+        if bin.mode == BucketMode::Trash {
+            return self.trash(raw);
+        }
+        let folder = self.bucket_folder(bin, raw);
+        self.create_folder(&folder);
+        // a RAW is its own RAW here, so only the first destination is used
+        let dest = match self.resolve_destination(folder, raw) {
+            Destination::Identical(dest, _) | Destination::Paths(dest, ..) => dest,
+            Destination::Skipped(_) => return Err(io::ErrorKind::AlreadyExists.into()),
+        };
+        match &self.dry_run {
+            Some(dry_run) => {
+                dry_run.record("move", raw, Some(&dest));
+                Ok(())
+            }
+            None => transfer::move_file(raw, &dest),
+        }
+    }
+
+    /// Where deleted pictures go, None for the trash
+    pub fn delete_destination(&self) -> Option<String> {
+        self.bucket(bucket::BIN)
+            .filter(|bin| bin.mode != BucketMode::Trash)
+            .map(|bin| bin.folder.to_string())
+    }
+
+    /// Sends a picture (and its RAW) to a bucket. Returns the report and wether the picture
+    /// left the folder
+    fn send_picture(&self, bucket: &Bucket, picture: &Path) -> (Report, bool) {
+        if !self.exists(picture) {
+            return (Report::new(Message::AlreadyGone, bucket.mode), false);
+        }
+        let sent = match bucket.mode {
+            BucketMode::Trash => self.move_to_trash(picture),
+            _ => self.transfer_to_folder(bucket, picture),
+        };
+        match sent {
+            Ok(report) => (
+                report,
+                matches!(bucket.mode, BucketMode::Move | BucketMode::Trash),
+            ),
+            Err(report) => (report, false),
+        }
+    }
+
+    /// Folder of a bucket for a picture, relative paths being inside the browsed folder except
+    /// for the edit and bin folders, next to the picture
+    fn bucket_folder(&self, bucket: &Bucket, picture: &Path) -> PathBuf {
+        let base = match bucket.name == bucket::EDIT || bucket.name == bucket::BIN {
+            true => self.folder_of(picture),
+            false => &self.folder,
+        };
+        bucket.folder.resolve(base, picture)
+    }
+
+    /// Folder of a picture, pictures listed from several folders having their own edit and bin
+    /// folders
+    fn folder_of<'a>(&'a self, picture: &'a Path) -> &'a Path {
+        picture
+            .parent()
+            .filter(|parent| parent.is_absolute())
+            .unwrap_or(&self.folder)
+    }
+
+    /// Copies, moves or links a picture and its RAW to the folder of a bucket, solving name
+    /// conflicts (Err if the JPG could not be)
+    fn transfer_to_folder(&self, bucket: &Bucket, picture: &Path) -> Result<Report, Report> {
+        let folder = self.bucket_folder(bucket, picture);
+        self.create_folder(&folder);
+        let moving = bucket.mode == BucketMode::Move;
+        let (dest1, dest2, conflict) = match self.resolve_destination(folder, picture) {
+            // same picture already there, replacing it with a move loses nothing
+            Destination::Identical(dest1, dest2) if moving => (dest1, dest2, Conflict::None),
+            Destination::Identical(..) => {
+                return Err(Report::new(Message::AlreadyThere, bucket.mode))
+            }
+            Destination::Skipped(dest1) => {
+                return Err(Report::new(Message::Skipped, bucket.mode).at(Conflict::None, &dest1))
+            }
+            Destination::Paths(dest1, dest2, conflict) => (dest1, dest2, conflict),
+        };
+        let (file1, file2) = self.get_files(picture);
+        if moving {
+            return self
+                .move_files(file1, file2, dest1.clone(), dest2)
+                .map(|report| report.at(conflict, &dest1))
+                .map_err(|report| report.at(conflict, &dest1));
+        }
+        let has_raw = self.exists(&file2);
+        let mut files = vec![(file1, dest1.clone())];
+        if has_raw {
+            files.push((file2, dest2));
+        }
+        let mut staged = Vec::new();
+        for (file, dest) in files.iter() {
+            match self.stage_file(bucket.mode, file, dest) {
+                Ok(file_staged) => staged.push(file_staged),
+                Err(err) => {
+                    // the picture and its RAW are put together or not at all
+                    for (path, ..) in staged {
+                        let _ = fs::remove_file(path);
+                    }
+                    let message = match err.kind() {
+                        io::ErrorKind::InvalidData => Message::Corrupted,
+                        _ => Message::Failed,
+                    };
+                    return Err(Report::new(message, bucket.mode).at(conflict, &dest1));
+                }
+            }
+        }
+        // both are there and checked, they can take the place of the files to overwrite
+        let placed: Vec<PathBuf> = files.iter().map(|(_, dest)| dest.clone()).collect();
+        for (i, ((path, ..), dest)) in staged.iter().zip(placed.iter()).enumerate() {
+            if path == dest {
+                continue;
+            }
+            if let Err(err) = fs::rename(path, dest) {
+                println!("Could not replace {}: {}", dest.display(), err);
+                // new files are removed, a file already replaced can't be brought back
+                for (j, ((path, ..), dest)) in staged.iter().zip(placed.iter()).enumerate() {
+                    if path == dest {
+                        let _ = fs::remove_file(dest);
+                    } else if j >= i {
+                        let _ = fs::remove_file(path);
+                    }
+                }
+                return Err(Report::new(Message::Failed, bucket.mode).at(conflict, &dest1));
+            }
+        }
+        let mut used_mode = bucket.mode;
+        for ((_, file_mode, hash), dest) in staged.iter().zip(placed.iter()) {
+            // only one of them fell back to a copy
+            if *file_mode != bucket.mode {
+                used_mode = BucketMode::Copy;
+            }
+            if let Some(hash) = hash {
+                if let Err(err) = checksum::record(dest, hash) {
+                    println!(
+                        "Could not record the checksum of {}: {}",
+                        dest.display(),
+                        err
+                    );
+                }
+            }
+        }
+        let message = match has_raw {
+            true => Message::Sent,
+            false => Message::SentNoRAW,
+        };
+        Ok(Report::new(message, used_mode).at(conflict, &dest1))
+    }
+
+    /// Copies or links a file with mode, next to dest if a file is in the way (to overwrite).
+    /// When verification is on, the original is hashed before it is copied, so the copy is
+    /// checked against what was read from the disk rather than from the cache. Returns where
+    /// the file was put, the mode used and its hash (Err InvalidData if the copy differs, it
+    /// is then removed)
+    fn stage_file(
+        &self,
+        mode: BucketMode,
+        file: &Path,
+        dest: &Path,
+    ) -> io::Result<(PathBuf, BucketMode, Option<blake3::Hash>)> {
+        if let Some(dry_run) = &self.dry_run {
+            // logged where it would end up, there is nothing to check
+            if !self.exists(file) {
+                return Err(io::ErrorKind::NotFound.into());
+            }
+            dry_run.record(operation_name(mode), file, Some(dest));
+            return Ok((dest.to_path_buf(), mode, None));
+        }
+        let path = match dest.exists() {
+            true => transfer::staging_path(dest),
+            false => dest.to_path_buf(),
+        };
+        let hash = match self.verify_copies {
+            true => Some(checksum::hash_file(file)?),
+            false => None,
+        };
+        let used_mode = bucket::transfer(mode, file, &path)?;
+        if let Some(hash) = &hash {
+            if !checksum::matches(&path, hash).unwrap_or(false) {
+                let _ = fs::remove_file(&path);
+                return Err(io::ErrorKind::InvalidData.into());
+            }
+        }
+        Ok((path, used_mode, hash))
+    }
 
+    /// Moves a picture and its RAW (if any) to their destinations, either both are moved or none.
+    /// Files already at a destination (identical or to overwrite) are only replaced once all
+    /// the files are moved next to them
+    fn move_files(
+        &self,
+        file1: PathBuf,
+        file2: PathBuf,
+        dest1: PathBuf,
+        dest2: PathBuf,
+    ) -> Result<Report, Report> {
+        let report = |message| Report::new(message, BucketMode::Move);
+        let has_raw = self.exists(&file2);
+        let mut moves = vec![(file1, dest1)];
+        if has_raw {
+            moves.push((file2, dest2));
+        }
+        let moved = if let Some(dry_run) = &self.dry_run {
+            for (file, dest) in moves.iter() {
+                dry_run.record("move", file, Some(dest));
+            }
+            Ok(())
+        } else if moves.iter().all(|(_, dest)| !dest.exists()) {
+            transfer::move_all(&moves)
+        } else {
+            let staged: Vec<PathBuf> = moves
+                .iter()
+                .map(|(_, dest)| transfer::staging_path(dest))
+                .collect();
+            transfer::move_all_via(&moves, &staged)
+        };
+        if moved.is_err() {
+            return Err(report(Message::Failed));
+        }
+        match has_raw {
+            true => Ok(report(Message::Sent)),
+            false => Ok(report(Message::SentNoRAW)),
+        }
+    }
+
+    /// Copy of a picture in the folder of the bucket with this name, under its name or one it
+    /// was renamed to. None if it was not copied there
+    pub fn copy_in(&self, name: &str, picture: &Path) -> Option<PathBuf> {
+        let folder = self.bucket_folder(self.bucket(name)?, picture);
+        let dest = folder.join(picture.file_name()?);
+        // checksums recorded when copying spare reading every copy
+        let manifest = checksum::read_manifest(&folder).unwrap_or_default();
+        let hash = std::cell::OnceCell::new();
+        (0..)
+            .map(|n| match n {
+                0 => dest.clone(),
+                n => with_suffix(&dest, n),
+            })
+            .take_while(|candidate| candidate.exists())
+            .find(|candidate| {
+                let name = candidate.file_name().unwrap_or_default().to_string_lossy();
+                match manifest.iter().find(|(_, entry)| *entry == name) {
+                    Some((recorded, _)) => hash
+                        .get_or_init(|| checksum::hash_file(picture).ok())
+                        .is_some_and(|hash| hash.to_hex().as_str() == recorded),
+                    None => transfer::same_content(picture, candidate).unwrap_or(false),
+                }
+            })
+    }
+
+    /// Destination of a picture and its RAW in folder, solving name conflicts
+    /// with the conflict policy
+    fn resolve_destination(&self, folder: PathBuf, picture: &Path) -> Destination {
+        let (file1, file2, dest1, dest2) = self.get_move_paths(folder, picture);
+        let has_raw = self.exists(&file2);
+        let taken =
+            |dest1: &Path, dest2: &Path| self.exists(dest1) || (has_raw && self.exists(dest2));
+        let same = |file: &Path, dest: &Path| self.same_content(file, dest);
+        let identical = |dest1: &Path, dest2: &Path| {
+            same(&file1, dest1) && (!has_raw || !self.exists(dest2) || same(&file2, dest2))
+        };
+        if !taken(&dest1, &dest2) {
+            return Destination::Paths(dest1, dest2, Conflict::None);
+        }
+        if identical(&dest1, &dest2) {
+            return Destination::Identical(dest1, dest2);
+        }
+        match self.conflict_policy {
+            ConflictPolicy::Skip => Destination::Skipped(dest1),
+            // files in the way are replaced once the new ones are there
+            ConflictPolicy::Overwrite => Destination::Paths(dest1, dest2, Conflict::Overwritten),
+            ConflictPolicy::Rename => {
+                // picture might already be there under one of the new names
+                let mut n = 1;
+                loop {
+                    let (new1, new2) = (with_suffix(&dest1, n), with_suffix(&dest2, n));
+                    if !taken(&new1, &new2) {
+                        return Destination::Paths(new1, new2, Conflict::Renamed);
+                    }
+                    if identical(&new1, &new2) {
+                        return Destination::Identical(new1, new2);
+                    }
+                    n += 1;
+                }
+            }
+        }
+    }
+
+    /// Sends a picture to the trash with its RAW and sidecars (Err if one of them could
+    /// not be sent, the JPG going last so it is only gone once they are)
+    fn move_to_trash(&self, picture: &Path) -> Result<Report, Report> {
+        let report = |message| Report::new(message, BucketMode::Trash);
+        let (file1, file2) = self.get_files(picture);
+        let has_raw = self.exists(&file2);
+        let folder = match file1.parent() {
+            Some(folder) if !folder.as_os_str().is_empty() => folder,
+            _ => Path::new("."),
+        };
+        let listed: Vec<PathBuf> = fs::read_dir(folder)
+            .map_err(|_| report(Message::Failed))?
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| self.exists(path))
+            .collect();
+        let mut files = sidecars_of(&listed, &file1, has_raw.then_some(file2.as_path()));
+        if has_raw {
+            files.push(file2);
+        }
+        files.push(file1);
         for file in files {
-            let f_path = file.unwrap().path();
-            if f_path.extension().is_none() {
+            if self.trash(&file).is_err() {
+                return Err(report(Message::Failed));
+            }
+        }
+        match has_raw {
+            true => Ok(report(Message::Sent)),
+            false => Ok(report(Message::SentNoRAW)),
+        }
+    }
+
+    /// Wether sending a picture to the bucket with this name takes it out of the folder
+    fn leaves_folder(&self, name: &str) -> bool {
+        self.buckets.iter().any(|bucket| {
+            bucket.name == name && matches!(bucket.mode, BucketMode::Move | BucketMode::Trash)
+        })
+    }
+
+    /// Path of a picture in the session, relative to the folder when inside it
+    pub fn session_file(&self, picture: &Path) -> PathBuf {
+        picture
+            .strip_prefix(&self.folder)
+            .unwrap_or(picture)
+            .to_path_buf()
+    }
+
+    /// How the bucket with this name sends a picture, and where (relative to the folder when
+    /// inside it)
+    fn plan(&self, name: &str, picture: &Path) -> (BucketMode, String) {
+        let Some(bucket) = self.buckets.iter().find(|bucket| bucket.name == name) else {
+            // bucket no longer set up, its decisions can't be applied
+            return (BucketMode::Copy, name.into());
+        };
+        if bucket.mode == BucketMode::Trash {
+            return (BucketMode::Trash, "trash".into());
+        }
+        let folder = self.bucket_folder(bucket, picture);
+        let folder = folder.strip_prefix(&self.folder).unwrap_or(&folder);
+        (bucket.mode, folder.display().to_string())
+    }
+
+    /// What applying the decisions of session will do: transfers per destination with the
+    /// number of pictures and their size, and every decision
+    pub fn review(&self, session: &Session) -> (Vec<PlannedTransfer>, Vec<String>) {
+        let mut summary: Vec<(BucketMode, String, usize, u64)> = Vec::new();
+        let mut decisions = Vec::new();
+        for picture in session.pictures.iter() {
+            let path = self.folder.join(&picture.file);
+            let (file1, file2) = self.get_files(&path);
+            let size: u64 = [file1, file2]
+                .iter()
+                .filter_map(|file| fs::metadata(file).ok())
+                .map(|metadata| metadata.len())
+                .sum();
+            for name in picture.buckets.iter() {
+                let (mode, destination) = self.plan(name, &path);
+                decisions.push(format!("{} → {}", picture.file.display(), destination));
+                match summary
+                    .iter_mut()
+                    .find(|(other, dest, ..)| *other == mode && *dest == destination)
+                {
+                    Some((.., count, total)) => {
+                        *count += 1;
+                        *total += size;
+                    }
+                    None => summary.push((mode, destination, 1, size)),
+                }
+            }
+        }
+        let summary = summary
+            .into_iter()
+            .map(|(mode, destination, count, size)| PlannedTransfer {
+                mode,
+                destination: destination.into(),
+                count: count as i32,
+                size: human_size(size).into(),
+            })
+            .collect();
+        (summary, decisions)
+    }
+
+    /// Applies all the decisions of session in one go, copies of a picture before it is moved.
+    /// A picture is only moved once all its copies succeeded
+    pub fn apply(&self, session: Session) -> Applied {
+        let mut outcome = Applied::default();
+        for mut picture in session.pictures {
+            let path = self.folder.join(&picture.file);
+            let failed = |name: &str| {
+                let (_, destination) = self.plan(name, &path);
+                format!("{} → {}", picture.file.display(), destination)
+            };
+            // removed by another program or taken in another folder, kept to be tried again
+            if !self.exists(&path) {
+                outcome
+                    .failed
+                    .extend(picture.buckets.iter().map(|name| failed(name)));
+                outcome.remaining.pictures.push(picture);
                 continue;
             }
-            let ext = f_path.extension().unwrap().to_str().unwrap().to_lowercase();
-            if ext == "jpg" || ext == "png" || ext == "jpeg"{
-                pic_list.push(f_path);
+            picture.buckets.sort_by_key(|name| self.leaves_folder(name));
+            let mut left = Vec::new();
+            for name in picture.buckets.iter() {
+                // a picture whose copy failed stays in the folder, so it can be tried again
+                let succeeded = if self.leaves_folder(name) && !left.is_empty() {
+                    false
+                } else {
+                    let (succeeded, moved) = self.apply_decision(name, &path);
+                    if moved {
+                        outcome.removed.push(path.clone());
+                    }
+                    succeeded
+                };
+                if succeeded {
+                    outcome.applied += 1;
+                } else {
+                    outcome.failed.push(failed(name));
+                    left.push(name.clone());
+                }
+            }
+            if !left.is_empty() {
+                picture.buckets = left;
+                outcome.remaining.pictures.push(picture);
             }
         }
-        pic_list.sort();
-        if pic_list.is_empty() {
-            panic!("Folder was empty")
+        outcome
+    }
+
+    /// Sends a picture to the bucket with this name. Returns wether it succeeded and wether
+    /// the picture left the folder
+    fn apply_decision(&self, name: &str, picture: &Path) -> (bool, bool) {
+        match self.buckets.iter().find(|bucket| bucket.name == name) {
+            Some(bucket) => {
+                let (report, moved) = self.send_picture(bucket, picture);
+                (!report.message.failed(), moved)
+            }
+            None => (false, false),
         }
-        let buffer = CircularBuffer::new(pic_list);
-        let mut edit_folder = folder_path.clone();
-        edit_folder.push(edit_folder_name);
-        let mut delete_folder = folder_path.clone();
-        delete_folder.push(delete_folder_name);
-        Self {
-            buffer,
-            edit_folder,
-            delete_folder,
-            current_folder: folder_path,
-            current_name: String::new(),
+    }
+
+    /// Makes sure a destination folder exists (only logged in a dry run)
+    fn create_folder(&self, folder: &Path) {
+        match &self.dry_run {
+            Some(dry_run) if !folder.exists() => dry_run.record_folder(folder),
+            Some(_) => (),
+            None => {
+                let _ = fs::create_dir_all(folder);
+            }
         }
     }
 
-    pub fn set_edit_folder(&mut self, name: String) {
-        self.edit_folder.set_file_name(name);
+    /// Sends a file to the trash (only logged in a dry run)
+    fn trash(&self, file: &Path) -> io::Result<()> {
+        let Some(dry_run) = &self.dry_run else {
+            return trash::delete(file).map_err(io::Error::other);
+        };
+        if !self.exists(file) {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        dry_run.record("trash", file, None);
+        Ok(())
     }
-    pub fn get_edit_folder(&mut self) -> String {
-        self.edit_folder
-            .file_name()
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .into()
+
+    /// Wether a file exists, once the operations of a dry run are taken into account
+    fn exists(&self, path: &Path) -> bool {
+        let simulated = self
+            .dry_run
+            .as_ref()
+            .and_then(|dry_run| dry_run.exists(path));
+        simulated.unwrap_or_else(|| path.exists())
     }
-    pub fn set_delete_folder(&mut self, name: String) {
-        self.delete_folder.set_file_name(name);
+
+    /// Compares two files, files created by a dry run having the content of their original
+    fn same_content(&self, a: &Path, b: &Path) -> bool {
+        let real = |path: &Path| {
+            let simulated = self
+                .dry_run
+                .as_ref()
+                .and_then(|dry_run| dry_run.content_of(path));
+            simulated.unwrap_or_else(|| path.to_path_buf())
+        };
+        transfer::same_content(&real(a), &real(b)).unwrap_or(false)
     }
-    pub fn get_delete_folder(&mut self) -> String {
-        self.delete_folder
-            .file_name()
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .into()
+
+    /// RAW next to a picture (first one found with the RAW extensions, in any case)
+    pub fn raw_of(&self, picture: &Path) -> Option<PathBuf> {
+        let folder = match picture.parent() {
+            Some(folder) if !folder.as_os_str().is_empty() => folder,
+            _ => Path::new("."),
+        };
+        let files: Vec<PathBuf> = fs::read_dir(folder)
+            .ok()?
+            .flatten()
+            .map(|entry| picture.with_file_name(entry.file_name()))
+            .collect();
+        self.raw_in(picture, &files)
+    }
+
+    /// RAW of a picture among listed files, the extension in any case
+    pub fn raw_in(&self, picture: &Path, files: &[PathBuf]) -> Option<PathBuf> {
+        let candidates: Vec<&PathBuf> = files
+            .iter()
+            .filter(|file| file.parent() == picture.parent())
+            .filter(|file| file.file_stem() == picture.file_stem() && self.exists(file))
+            .collect();
+        self.raw_extensions.iter().find_map(|raw_ext| {
+            candidates
+                .iter()
+                .find(|file| {
+                    file.extension()
+                        .is_some_and(|ext| ext.eq_ignore_ascii_case(raw_ext))
+                })
+                .map(|file| file.to_path_buf())
+        })
+    }
+
+    /// Wether a file has one of the RAW extensions, in any case
+    pub fn is_raw(&self, path: &Path) -> bool {
+        let Some(ext) = path.extension() else {
+            return false;
+        };
+        self.raw_extensions
+            .iter()
+            .any(|raw_ext| ext.eq_ignore_ascii_case(raw_ext))
+    }
+
+    /// A picture and its RAW, where it would be if there is none
+    fn get_files(&self, picture: &Path) -> (PathBuf, PathBuf) {
+        let file1 = picture.to_path_buf();
+        let file2 = self
+            .raw_of(&file1)
+            .unwrap_or_else(|| file1.with_extension(&self.raw_extensions[0]));
+        (file1, file2)
+    }
+
+    fn get_move_paths(
+        &self,
+        folder_move: PathBuf,
+        picture: &Path,
+    ) -> (PathBuf, PathBuf, PathBuf, PathBuf) {
+        let (file1, file2) = self.get_files(picture);
+
+        let mut dest1 = folder_move.clone();
+        dest1.push(file1.file_name().unwrap_or_default());
+        let mut dest2 = folder_move;
+        dest2.push(file2.file_name().unwrap_or_default());
+        (file1, file2, dest1, dest2)
+    }
+}
+
+impl AppLogic {
+    pub fn new(sorter: Sorter, sort: SortOrder, buffer_size: usize) -> Result<Self, PhogError> {
+        let pic_list = list_pictures(&sorter.folder, sort)?;
+        Self::with_pictures(sorter, pic_list, buffer_size)
+    }
+
+    /// Browses only the given pictures, in this order
+    pub fn with_pictures(
+        sorter: Sorter,
+        pic_list: Vec<PathBuf>,
+        buffer_size: usize,
+    ) -> Result<Self, PhogError> {
+        let buffer = CircularBuffer::new(pic_list, buffer_size)?;
+        Ok(Self {
+            buffer,
+            sorter,
+            session: None,
+            current_path: PathBuf::new(),
+            rename_plan: None,
+            unshuffled: None,
+        })
+    }
+
+    pub fn sorter(&self) -> &Sorter {
+        &self.sorter
+    }
+
+    pub fn sorter_mut(&mut self) -> &mut Sorter {
+        &mut self.sorter
+    }
+
+    /// Folder a template gives for the current picture, to be shown before it is used
+    pub fn preview_folder(&self, template: &str) -> Result<String, String> {
+        let template: FolderTemplate = template.parse()?;
+        let folder = template.resolve(
+            self.sorter.folder_of(&self.current_path),
+            &self.current_path,
+        );
+        Ok(folder.display().to_string())
     }
 
     pub async fn next_img(&mut self) -> bool {
@@ -77,63 +890,843 @@ impl AppLogic {
         self.buffer.prev_img().await
     }
 
-    pub fn edit(&self) -> Message {
-        let _ = fs::create_dir_all(self.edit_folder.clone());
-        let (file1, file2, dest1, dest2) = self.get_current_move_path(self.edit_folder.clone());
-        if fs::exists(dest1.clone()).unwrap() {
-            return Message::EditAlreadyDone;
+    /// Sends current picture (and its RAW) to the bucket triggered by key, or only marks it
+    /// when decisions are deferred. Returns None if no bucket uses this key, else the report,
+    /// the bucket and wether there are pictures left
+    pub async fn send_to_bucket(&mut self, key: &str) -> Option<(Report, Bucket, bool)> {
+        let bucket = self
+            .sorter
+            .buckets
+            .iter()
+            .find(|bucket| bucket.key == key)?
+            .clone();
+        if self.session.is_some() {
+            let message = match self.toggle_decision(&bucket) {
+                true => Message::Marked,
+                false => Message::Unmarked,
+            };
+            return Some((Report::new(message, bucket.mode), bucket, true));
         }
-        if fs::copy(file1, &dest1).is_err() {
-            return Message::EditFailed;
+        let (report, moved) = self.sorter.send_picture(&bucket, &self.current_path);
+        // moved pictures are not in the folder anymore
+        let left = !moved || self.buffer.delete().await;
+        Some((report, bucket, left))
+    }
+
+    /// Takes decisions instead of acting right away, resuming the session saved in the folder
+    pub fn defer_decisions(&mut self) -> Result<(), PhogError> {
+        let mut session = Session::load(&self.sorter.folder.join(SESSION_NAME))?;
+        // pictures removed since the session was saved
+        session
+            .pictures
+            .retain(|picture| self.sorter.folder.join(&picture.file).exists());
+        self.session = Some(session);
+        Ok(())
+    }
+
+    /// Takes or cancels the decision to send the current picture to a bucket. A picture can
+    /// only leave the folder one way, so the other decisions moving it are cancelled.
+    /// Returns wether it is now taken
+    fn toggle_decision(&mut self, bucket: &Bucket) -> bool {
+        let file = self.sorter.session_file(&self.current_path);
+        let Some(mut session) = self.session.take() else {
+            return false;
+        };
+        let taken = session.toggle(&file, &bucket.name);
+        if taken && self.sorter.leaves_folder(&bucket.name) {
+            session.remove(&file, |name| {
+                name != bucket.name && self.sorter.leaves_folder(name)
+            });
         }
-        if fs::copy(file2, &dest2).is_err() {
-            return Message::EditNoRAW;
+        self.save_session(&session);
+        self.session = Some(session);
+        taken
+    }
+
+    /// Marks pictures for the edit folder, e.g. the ones chosen by a client (deferred mode only).
+    /// Returns the number of pictures newly marked
+    pub fn pick(&mut self, pictures: &[PathBuf]) -> usize {
+        let Some(mut session) = self.session.take() else {
+            return 0;
+        };
+        let picked = pictures
+            .iter()
+            .filter(|picture| session.take(&self.sorter.session_file(picture), bucket::EDIT))
+            .count();
+        self.save_session(&session);
+        self.session = Some(session);
+        picked
+    }
+
+    /// Number of decisions not applied yet
+    pub fn pending(&self) -> usize {
+        self.session.as_ref().map_or(0, Session::pending)
+    }
+
+    /// Where the decisions on the current picture send it, for the caption
+    pub fn current_marks(&self) -> String {
+        let Some(session) = &self.session else {
+            return String::new();
+        };
+        session
+            .buckets(&self.sorter.session_file(&self.current_path))
+            .iter()
+            .map(|name| self.sorter.plan(name, &self.current_path).1)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// What applying the pending decisions will do: transfers per destination with the number
+    /// of pictures and their size, and every decision
+    pub fn review(&self) -> (Vec<PlannedTransfer>, Vec<String>) {
+        match &self.session {
+            Some(session) => self.sorter.review(session),
+            None => (Vec::new(), Vec::new()),
         }
-        Message::EditSuccessful
     }
 
-    pub async fn delete(&mut self) -> (Message, bool) {
-        let _ = fs::create_dir_all(self.delete_folder.clone());
-        let (file1, file2, dest1, dest2) = self.get_current_move_path(self.delete_folder.clone());
-        if !fs::exists(file1.clone()).unwrap() {
-            return (Message::BinAlreadyDone, true);
+    /// Applies all the pending decisions in one go, decisions that failed stay pending.
+    /// Returns the number of decisions applied and failed, and wether there are pictures left
+    pub async fn apply(&mut self) -> (usize, usize, bool) {
+        let Some(session) = self.session.take() else {
+            return (0, 0, true);
+        };
+        let outcome = self.sorter.apply(session);
+        self.save_session(&outcome.remaining);
+        self.session = Some(outcome.remaining);
+        let mut pictures_left = true;
+        for path in outcome.removed {
+            pictures_left &= self.remove_image(&path).await;
         }
-        if fs::rename(file1, &dest1).is_err() {
-            return (Message::BinFailed, true);
+        (outcome.applied, outcome.failed.len(), pictures_left)
+    }
+
+    /// Pictures renamed in the app: the ones marked for the edit folder or a bucket, in
+    /// browsing order, else the current one
+    fn rename_selection(&self) -> Vec<PathBuf> {
+        let marked: Vec<PathBuf> = match &self.session {
+            Some(session) => self
+                .buffer
+                .pic_list()
+                .iter()
+                .filter(|picture| {
+                    session
+                        .buckets(&self.sorter.session_file(picture))
+                        .iter()
+                        .any(|name| name != bucket::BIN)
+                })
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        };
+        match marked.is_empty() {
+            true => vec![self.current_path.clone()],
+            false => marked,
         }
-        let status = if fs::rename(file2, &dest2).is_err() {
-            Message::BinNoRAW
-        } else {
-            Message::BinSuccessful
+    }
+
+    /// Plans renaming the selection with a pattern, counted from 1, kept until it is confirmed
+    pub fn plan_rename(&mut self, pattern: &RenamePattern) -> &RenamePlan {
+        let plan = RenamePlan::new(&self.sorter, &self.rename_selection(), pattern, 1);
+        self.rename_plan.insert(plan)
+    }
+
+    /// New name of a picture of the selection (the current one if selected) with a pattern,
+    /// as `old → new`, to be shown before it is used
+    pub fn preview_rename(&self, pattern: &str) -> Result<String, String> {
+        let pattern: RenamePattern = pattern.parse()?;
+        let selection = self.rename_selection();
+        let counter = selection
+            .iter()
+            .position(|picture| *picture == self.current_path)
+            .unwrap_or(0);
+        let picture = &selection[counter];
+        let old_name = picture.file_name().unwrap_or_default().to_string_lossy();
+        let mut name = pattern
+            .name(picture, counter + 1)
+            .map_err(|err| format!("{}: {}", old_name, err))?;
+        if let Some(ext) = picture.extension() {
+            name = format!("{}.{}", name, ext.to_string_lossy());
+        }
+        Ok(format!("{} → {}", old_name, name))
+    }
+
+    /// Renames the pictures of the confirmed plan with their RAW and sidecars, all or none,
+    /// following them in the browsed list and the pending decisions. Returns how many were
+    pub async fn rename(&mut self) -> io::Result<usize> {
+        let Some(plan) = self.rename_plan.take() else {
+            return Ok(0);
+        };
+        plan.apply(&self.sorter)?;
+        self.follow_renames(&plan).await;
+        Ok(plan.renames.len())
+    }
+
+    /// Gives back their names to the pictures renamed last in the folder. Returns how many were
+    pub async fn undo_rename(&mut self) -> io::Result<usize> {
+        let Some(plan) = RenamePlan::undo_last(&self.sorter)? else {
+            return Ok(0);
         };
-        (status, self.buffer.delete().await)
+        self.follow_renames(&plan).await;
+        Ok(plan.renames.len())
     }
+
+    /// Follows renamed pictures in the browsed list and the pending decisions
+    async fn follow_renames(&mut self, plan: &RenamePlan) {
+        if self.sorter.dry_run.is_some() {
+            return;
+        }
+        for rename in plan.renames.iter() {
+            let Some((_, new_path)) = rename.files.first() else {
+                continue;
+            };
+            if let Some(index) = self.buffer.position(&rename.picture) {
+                self.buffer.rename(index, new_path.clone()).await;
+            }
+            if let Some(session) = &mut self.session {
+                session.rename(
+                    &self.sorter.session_file(&rename.picture),
+                    &self.sorter.session_file(new_path),
+                );
+            }
+            if self.current_path == rename.picture {
+                self.current_path = new_path.clone();
+            }
+        }
+        if let Some(session) = &self.session {
+            self.save_session(session);
+        }
+    }
+
+    /// Keeps the session on disk so decisions survive a restart (not in a dry run)
+    fn save_session(&self, session: &Session) {
+        if self.sorter.dry_run.is_some() {
+            return;
+        }
+        if let Err(err) = session.save(&self.sorter.folder.join(SESSION_NAME)) {
+            println!("Could not save the session: {}", err);
+        }
+    }
+
+    /// Adds a picture that appeared in the folder (returns false if it was ignored)
+    pub async fn add_image(&mut self, path: PathBuf) -> bool {
+        if !is_picture(&path) || self.buffer.position(&path).is_some() {
+            return false;
+        }
+        self.buffer.push(path).await;
+        true
+    }
+
+    /// Removes a picture that disappeared from the folder (returns false if no picture is left)
+    pub async fn remove_image(&mut self, path: &Path) -> bool {
+        match self.buffer.position(path) {
+            Some(index) => self.buffer.remove(index).await,
+            None => true,
+        }
+    }
+
+    /// Drops or reloads pictures that were removed or changed on disk by other programs
+    /// (returns false if no picture is left)
+    pub async fn sync(&mut self) -> bool {
+        self.buffer.sync().await
+    }
+
+    /// Drops or reloads the current picture if it was removed or changed on disk, before it is
+    /// shown (returns false if no picture is left)
+    pub async fn sync_current(&mut self) -> bool {
+        self.buffer.sync_current().await
+    }
+
+    pub async fn go_to_last(&mut self) {
+        self.buffer.go_to_last().await;
+    }
+
+    pub async fn go_to_first(&mut self) {
+        self.buffer.go_to_first().await;
+    }
+
+    /// Browses the pictures in a random order starting with the current one, each once, until
+    /// unshuffled. Shuffling again gives a new order
+    pub async fn shuffle(&mut self) {
+        let mut pictures = self.buffer.pic_list().to_vec();
+        if self.unshuffled.is_none() {
+            self.unshuffled = Some(pictures.clone());
+        }
+        let current = self.buffer.position(&self.current_path).unwrap_or(0);
+        pictures.swap(0, current);
+        // Fisher-Yates on the others, std hashers being seeded randomly
+        let random = RandomState::new();
+        for i in (2..pictures.len()).rev() {
+            let j = 1 + random.hash_one((Instant::now(), i)) as usize % i;
+            pictures.swap(i, j);
+        }
+        self.buffer.reorder(pictures, 0).await;
+    }
+
+    /// Browses the pictures in their order again, from the current one
+    pub async fn unshuffle(&mut self) {
+        let Some(order) = self.unshuffled.take() else {
+            return;
+        };
+        let browsed: HashSet<&PathBuf> = self.buffer.pic_list().iter().collect();
+        let mut pictures: Vec<PathBuf> = order
+            .into_iter()
+            .filter(|picture| browsed.contains(picture))
+            .collect();
+        // pictures that appeared meanwhile come last
+        let ordered: HashSet<&PathBuf> = pictures.iter().collect();
+        let added: Vec<PathBuf> = self
+            .buffer
+            .pic_list()
+            .iter()
+            .filter(|picture| !ordered.contains(picture))
+            .cloned()
+            .collect();
+        pictures.extend(added);
+        let index = pictures
+            .iter()
+            .position(|picture| *picture == self.current_path)
+            .unwrap_or(0);
+        self.buffer.reorder(pictures, index).await;
+    }
+
     pub async fn init(&mut self) {
         self.buffer.init().await;
     }
 
     pub async fn get_img(&mut self) -> ImageStat {
         let img = self.buffer.get_elem().await;
-        self.current_name = img.name.clone();
+        self.current_path = img.path.clone();
         img
     }
 
     pub fn get_first_img(&mut self) -> ImageStat {
         let img = self.buffer.get_first_elem();
-        self.current_name = img.name.clone();
+        self.current_path = img.path.clone();
         img
     }
+}
 
-    fn get_current_move_path(&self, folder_move: PathBuf) -> (PathBuf, PathBuf, PathBuf, PathBuf) {
-        let mut file1 = self.current_folder.clone();
-        file1.push(self.current_name.clone());
-        let mut file2 = file1.clone();
-        file2.set_extension("RAF");
+/// Pictures of a folder in browsing order
+pub fn list_pictures(folder: &Path, sort: SortOrder) -> Result<Vec<PathBuf>, PhogError> {
+    let files = fs::read_dir(folder)
+        .map_err(|err| PhogError::FolderScan(folder.to_path_buf(), err.to_string()))?;
+    let mut pic_list: Vec<PathBuf> = Vec::new();
 
-        let mut dest1 = folder_move.clone();
-        dest1.push(file1.file_name().unwrap());
-        let mut dest2 = folder_move;
-        dest2.push(file2.file_name().unwrap());
-        (file1, file2, dest1, dest2)
+    // entries that can't be read are skipped rather than failing the whole scan
+    for file in files.flatten() {
+        let f_path = file.path();
+        if is_picture(&f_path) {
+            pic_list.push(f_path);
+        }
+    }
+    match sort {
+        SortOrder::Name => pic_list.sort(),
+        SortOrder::Modified => pic_list.sort_by_cached_key(|path| {
+            let modified = fs::metadata(path).and_then(|metadata| metadata.modified());
+            (modified.ok(), path.clone())
+        }),
+    }
+    Ok(pic_list)
+}
+
+/// Pictures listed one per line (e.g. by find or fd), in this order, relative paths being
+/// from the current folder. Other files, missing ones and repeated ones are skipped
+pub fn read_pictures(list: impl BufRead) -> io::Result<Vec<PathBuf>> {
+    let mut pic_list: Vec<PathBuf> = Vec::new();
+    let mut seen = HashSet::new();
+    for line in list.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let path = std::path::absolute(line)?;
+        if is_picture(&path) && path.is_file() && seen.insert(path.clone()) {
+            pic_list.push(path);
+        }
+    }
+    Ok(pic_list)
+}
+
+/// Checks the extension of the file against supported picture formats
+pub(crate) fn is_picture(path: &Path) -> bool {
+    let Some(ext) = path.extension() else {
+        return false;
+    };
+    let ext = ext.to_string_lossy().to_lowercase();
+    ext == "jpg" || ext == "png" || ext == "jpeg"
+}
+
+/// Name of the operation sending a file with mode, for the dry run log
+fn operation_name(mode: BucketMode) -> &'static str {
+    match mode {
+        BucketMode::Copy => "copy",
+        BucketMode::Move => "move",
+        BucketMode::Hardlink => "hardlink",
+        BucketMode::Link => "symlink",
+        BucketMode::Trash => "trash",
+    }
+}
+
+/// Size in bytes for people, e.g. `12.3 MB`
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "kB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Adds `_n` at the end of the file name (before the extension)
+pub(crate) fn with_suffix(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.file_stem().unwrap_or_default().to_os_string();
+    name.push(format!("_{}", n));
+    if let Some(ext) = path.extension() {
+        name.push(".");
+        name.push(ext);
+    }
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Sorter of folder with only the built-in buckets, sent to `edit` and `bin`
+    pub(crate) fn sorter(folder: &Path) -> Sorter {
+        Sorter::new(
+            folder.to_path_buf(),
+            "edit".parse().unwrap(),
+            "bin".parse().unwrap(),
+            Vec::new(),
+        )
+    }
+
+    #[test]
+    fn edit_and_bin_are_buckets() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.jpg"), "a").unwrap();
+        fs::write(dir.path().join("a.RAF"), "raf").unwrap();
+        fs::write(dir.path().join("b.jpg"), "b").unwrap();
+        let client: Bucket = "client:c:link".parse().unwrap();
+        let mut sorter = sorter(dir.path());
+        sorter.buckets.push(client);
+        sorter.set_edit_folder("picked").unwrap();
+        sorter.set_edit_mode(LinkMode::Hardlink);
+        let [edit, bin, client] = sorter.get_buckets() else {
+            panic!("edit, bin and client expected");
+        };
+        let (a, b) = (dir.path().join("a.jpg"), dir.path().join("b.jpg"));
+        // the name of the sent picture is tested with the conflicts
+        let send = |bucket, picture| {
+            let (report, moved) = sorter.send_picture(bucket, picture);
+            (report.message, report.mode, moved)
+        };
+        assert_eq!(send(edit, &a), (Message::Sent, BucketMode::Hardlink, false));
+        assert_eq!(
+            send(edit, &a),
+            (Message::AlreadyThere, BucketMode::Hardlink, false)
+        );
+        assert_eq!(
+            send(edit, &b),
+            (Message::SentNoRAW, BucketMode::Hardlink, false)
+        );
+        assert_eq!(send(client, &a), (Message::Sent, BucketMode::Link, false));
+        assert!(dir.path().join("client/a.RAF").is_symlink());
+        assert_eq!(send(bin, &a), (Message::Sent, BucketMode::Move, true));
+        assert_eq!(
+            send(bin, &a),
+            (Message::AlreadyGone, BucketMode::Move, false)
+        );
+        assert_eq!(fs::read(dir.path().join("picked/a.RAF")).unwrap(), b"raf");
+        assert!(dir.path().join("bin/a.RAF").exists());
+        assert!(!a.exists());
+    }
+
+    #[test]
+    fn folders_from_templates() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.jpg");
+        crate::template::tests::write_jpeg(&a, "2026:10:18 15:30:12", "X-T5");
+        let dated: Bucket = "dated:t:copy:{year}/{date}".parse().unwrap();
+        let mut sorter = sorter(dir.path());
+        sorter.buckets.push(dated);
+        let mut logic = AppLogic::new(sorter, SortOrder::Name, BUFFER_SIZE).unwrap();
+        logic.current_path = a.clone();
+        assert!(logic.sorter_mut().set_edit_folder("{camera").is_err());
+        assert_eq!(logic.sorter_mut().get_edit_folder(), "edit");
+        assert_eq!(
+            logic.preview_folder("/mnt/{camera}").unwrap(),
+            Path::new("/mnt/X-T5").display().to_string()
+        );
+        let sorter = logic.sorter();
+        let dated = &sorter.get_buckets()[2];
+        assert_eq!(sorter.send_picture(dated, &a).0.message, Message::SentNoRAW);
+        assert!(dir.path().join("2026/2026-10-18/a.jpg").exists());
+    }
+
+    #[test]
+    fn raw_extensions_and_sort_order() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["b.jpg", "a.jpg", "a.dng", "a.RAF"] {
+            fs::write(dir.path().join(name), name).unwrap();
+        }
+        // a.jpg written last
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(60);
+        fs::File::options()
+            .write(true)
+            .open(dir.path().join("a.jpg"))
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        let new = |sort| AppLogic::new(sorter(dir.path()), sort, BUFFER_SIZE).unwrap();
+        let mut logic = new(SortOrder::Modified);
+        assert_eq!(logic.get_first_img().path, dir.path().join("b.jpg"));
+        let sorter = logic.sorter_mut();
+        sorter.set_raw_extensions(vec!["DNG".into(), "RAF".into()]);
+        let (_, raw) = sorter.get_files(&dir.path().join("a.jpg"));
+        assert_eq!(raw, dir.path().join("a.dng"));
+        let (_, raw) = sorter.get_files(&dir.path().join("b.jpg"));
+        assert_eq!(raw, dir.path().join("b.DNG"));
+        let mut logic = new(SortOrder::Name);
+        assert_eq!(logic.get_first_img().path, dir.path().join("a.jpg"));
+    }
+
+    #[test]
+    fn name_conflicts() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.jpg");
+        fs::write(&a, "a").unwrap();
+        fs::write(dir.path().join("a.RAF"), "raf").unwrap();
+        let mut sorter = sorter(dir.path());
+        let edit = sorter.get_buckets()[0].clone();
+        let folder = dir.path().join("edit");
+        fs::create_dir(&folder).unwrap();
+        // another card, same name
+        fs::write(folder.join("a.jpg"), "other").unwrap();
+        let sent = |message, conflict, name: &str| {
+            let report = Report::new(message, BucketMode::Copy);
+            (report.at(conflict, Path::new(name)), false)
+        };
+        assert_eq!(
+            sorter.send_picture(&edit, &a),
+            sent(Message::Sent, Conflict::Renamed, "a_1.jpg")
+        );
+        assert_eq!(fs::read(folder.join("a_1.RAF")).unwrap(), b"raf");
+        // already there under the new name
+        assert_eq!(
+            sorter.send_picture(&edit, &a).0.message,
+            Message::AlreadyThere
+        );
+
+        sorter.set_conflict_policy(ConflictPolicy::Skip);
+        fs::write(folder.join("a_1.jpg"), "changed").unwrap();
+        fs::remove_file(folder.join("a_1.RAF")).unwrap();
+        assert_eq!(
+            sorter.send_picture(&edit, &a),
+            sent(Message::Skipped, Conflict::None, "a.jpg")
+        );
+        assert_eq!(fs::read(folder.join("a.jpg")).unwrap(), b"other");
+
+        sorter.set_conflict_policy(ConflictPolicy::Overwrite);
+        assert_eq!(
+            sorter.send_picture(&edit, &a),
+            sent(Message::Sent, Conflict::Overwritten, "a.jpg")
+        );
+        assert_eq!(fs::read(folder.join("a.jpg")).unwrap(), b"a");
+        assert_eq!(fs::read(folder.join("a.RAF")).unwrap(), b"raf");
+        assert_eq!(fs::read_dir(&folder).unwrap().count(), 3);
+
+        // moving over an identical copy loses nothing
+        let bin = sorter.get_buckets()[1].clone();
+        fs::create_dir(dir.path().join("bin")).unwrap();
+        fs::copy(&a, dir.path().join("bin/a.jpg")).unwrap();
+        let moved = sorter.send_picture(&bin, &a);
+        assert_eq!(moved.0.message, Message::Sent);
+        assert!(moved.1);
+        assert!(!a.exists());
+        assert_eq!(fs::read_dir(dir.path().join("bin")).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn verified_copies_go_in_pairs() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a.jpg"), dir.path().join("b.jpg"));
+        for (name, content) in [
+            ("a.jpg", "a"),
+            ("a.RAF", "raf"),
+            ("b.jpg", "b"),
+            ("b.RAF", ""),
+        ] {
+            fs::write(dir.path().join(name), content).unwrap();
+        }
+        let mut sorter = sorter(dir.path());
+        sorter.set_verify_copies(true);
+        let edit = sorter.get_buckets()[0].clone();
+        assert_eq!(sorter.send_picture(&edit, &a).0.message, Message::Sent);
+        let folder = dir.path().join("edit");
+        assert_eq!(
+            checksum::verify_folder(&folder).unwrap(),
+            [
+                ("a.jpg".to_string(), checksum::FileCheck::Ok),
+                ("a.RAF".to_string(), checksum::FileCheck::Ok)
+            ]
+        );
+        // the RAW can't be copied (a folder is in the way), so the picture is not either
+        fs::create_dir(folder.join("b.RAF")).unwrap();
+        sorter.set_conflict_policy(ConflictPolicy::Overwrite);
+        assert_eq!(sorter.send_picture(&edit, &b).0.message, Message::Failed);
+        assert!(!folder.join("b.jpg").exists());
+        assert_eq!(fs::read_dir(&folder).unwrap().count(), 4);
+    }
+
+    #[test]
+    fn deferred_decisions() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a.jpg", "a.RAF", "b.jpg", "c.jpg"] {
+            fs::write(dir.path().join(name), name).unwrap();
+        }
+        let archive: Bucket = "archive:a:move".parse().unwrap();
+        let new = || {
+            let mut sorter = sorter(dir.path());
+            sorter.buckets.push(archive.clone());
+            AppLogic::new(sorter, SortOrder::Name, BUFFER_SIZE).unwrap()
+        };
+        let mut logic = new();
+        logic.defer_decisions().unwrap();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (a, b) = (dir.path().join("a.jpg"), dir.path().join("b.jpg"));
+        let mut mark = |picture: &Path, key: &str| {
+            logic.current_path = picture.to_path_buf();
+            let (report, ..) = runtime.block_on(logic.send_to_bucket(key)).unwrap();
+            report.message
+        };
+        assert_eq!(mark(&a, "d"), Message::Marked);
+        assert_eq!(mark(&a, "e"), Message::Marked);
+        // a picture leaves the folder only one way
+        assert_eq!(mark(&a, "a"), Message::Marked);
+        assert_eq!(mark(&b, "d"), Message::Marked);
+        assert_eq!(mark(&b, "d"), Message::Unmarked);
+        assert_eq!(mark(&b, "e"), Message::Marked);
+        logic.current_path = dir.path().join("a.jpg");
+        assert_eq!(logic.current_marks(), "edit, archive");
+        assert_eq!(logic.pending(), 3);
+        let (summary, decisions) = logic.review();
+        assert_eq!(summary[0].count, 2);
+        assert_eq!(summary[0].size, "15 B");
+        assert_eq!(human_size(12_345_678), "12.3 MB");
+        assert_eq!(summary[1].mode, BucketMode::Move);
+        assert_eq!(
+            decisions,
+            ["a.jpg → edit", "a.jpg → archive", "b.jpg → edit"]
+        );
+        // kept for the next time
+        let mut resumed = new();
+        resumed.defer_decisions().unwrap();
+        assert_eq!(resumed.pending(), 3);
+
+        let (applied, failed, left) = runtime.block_on(logic.apply());
+        assert_eq!((applied, failed, left), (3, 0, true));
+        assert_eq!(logic.pending(), 0);
+        assert!(!dir.path().join(SESSION_NAME).exists());
+        assert!(dir.path().join("edit/a.RAF").exists());
+        assert!(dir.path().join("archive/a.RAF").exists());
+        assert!(!dir.path().join("a.jpg").exists());
+        assert!(dir.path().join("edit/b.jpg").exists());
+    }
+
+    #[test]
+    fn failed_copy_keeps_the_picture() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.jpg");
+        fs::write(&a, "a").unwrap();
+        // the edit folder can't be created
+        fs::write(dir.path().join("edit"), "").unwrap();
+        let mut logic = AppLogic::new(sorter(dir.path()), SortOrder::Name, BUFFER_SIZE).unwrap();
+        logic.defer_decisions().unwrap();
+        logic.current_path = a.clone();
+        let edit = logic.sorter.buckets[0].clone();
+        let bin = logic.sorter.buckets[1].clone();
+        logic.toggle_decision(&bin);
+        logic.toggle_decision(&edit);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (applied, failed, left) = runtime.block_on(logic.apply());
+        assert_eq!((applied, failed, left), (0, 2, true));
+        assert!(a.exists());
+        assert!(!dir.path().join("bin").exists());
+        let session = Session::load(&dir.path().join(SESSION_NAME)).unwrap();
+        assert_eq!(session.buckets(Path::new("a.jpg")), ["edit", "bin"]);
+    }
+
+    #[test]
+    fn apply_a_session_file() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.jpg"), "a").unwrap();
+        fs::write(dir.path().join("b.jpg"), "b").unwrap();
+        let mut session = Session::default();
+        session.toggle(Path::new("a.jpg"), "edit");
+        session.toggle(Path::new("a.jpg"), "bin");
+        session.toggle(Path::new("gone.jpg"), "edit");
+        session.toggle(Path::new("b.jpg"), "unknown");
+        let outcome = sorter(dir.path()).apply(session);
+        assert_eq!(outcome.applied, 2);
+        assert_eq!(outcome.removed, [dir.path().join("a.jpg")]);
+        assert_eq!(outcome.failed, ["gone.jpg → edit", "b.jpg → unknown"]);
+        // kept to be tried again, e.g. with the right folder
+        assert_eq!(outcome.remaining.pending(), 2);
+        assert_eq!(outcome.remaining.buckets(Path::new("gone.jpg")), ["edit"]);
+        assert!(dir.path().join("edit/a.jpg").exists());
+        assert!(dir.path().join("bin/a.jpg").exists());
+    }
+
+    #[test]
+    fn dry_run_sees_its_own_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.jpg");
+        fs::write(&a, "a").unwrap();
+        fs::write(dir.path().join("a.RAF"), "raf").unwrap();
+        let mut sorter = sorter(dir.path());
+        sorter.set_dry_run();
+        sorter.set_verify_copies(true);
+        let edit = sorter.get_buckets()[0].clone();
+        let bin = sorter.get_buckets()[1].clone();
+        let send = |bucket| sorter.send_picture(bucket, &a).0.message;
+        assert_eq!(send(&edit), Message::Sent);
+        assert_eq!(send(&edit), Message::AlreadyThere);
+        assert_eq!(send(&bin), Message::Sent);
+        assert_eq!(send(&bin), Message::AlreadyGone);
+        let operations = sorter.get_dry_run().unwrap().operations();
+        assert_eq!(operations.len(), 6);
+        assert!(operations[1].starts_with("copy ") && operations[1].ends_with("(1 B)"));
+        // nothing was touched
+        assert!(a.exists());
+        assert!(!dir.path().join("edit").exists() && !dir.path().join("bin").exists());
+    }
+
+    #[test]
+    fn orphan_raws_in_any_case() {
+        let dir = tempfile::tempdir().unwrap();
+        for file in [
+            "a.jpg",
+            "a.raf",
+            "b.jpg",
+            "b.Raf.xmp",
+            "c.RAF",
+            "d.Raf",
+            "d.txt",
+        ] {
+            fs::write(dir.path().join(file), file).unwrap();
+        }
+        let sorter = sorter(dir.path());
+        let raw = |file: &str| sorter.raw_of(&dir.path().join(file));
+        assert_eq!(raw("a.jpg"), Some(dir.path().join("a.raf")));
+        assert_eq!(raw("b.jpg"), None);
+        assert_eq!(raw("d.jpg"), Some(dir.path().join("d.Raf")));
+        let raws = sorter.orphan_raws().unwrap();
+        assert_eq!(raws, [dir.path().join("c.RAF"), dir.path().join("d.Raf")]);
+        assert_eq!(sorter.delete_destination().as_deref(), Some("bin"));
+        assert!(sorter.remove_orphan_raws(&raws).is_empty());
+        assert_eq!(fs::read(dir.path().join("bin/c.RAF")).unwrap(), b"c.RAF");
+        assert!(!dir.path().join("d.Raf").exists());
+        assert!(sorter.orphan_raws().unwrap().is_empty());
+    }
+
+    #[test]
+    fn copies_found_by_checksum() {
+        let dir = tempfile::tempdir().unwrap();
+        let (picture, copy) = (dir.path().join("a.jpg"), dir.path().join("edit/a_1.jpg"));
+        fs::write(&picture, "a").unwrap();
+        fs::create_dir(dir.path().join("edit")).unwrap();
+        fs::write(dir.path().join("edit/a.jpg"), "other").unwrap();
+        fs::write(&copy, "a").unwrap();
+        let sorter = sorter(dir.path());
+        assert_eq!(sorter.copy_in("edit", &picture), Some(copy.clone()));
+        assert_eq!(sorter.copy_in("bin", &picture), None);
+        assert_eq!(sorter.copy_in("unknown", &picture), None);
+        // the manifest is trusted over the content
+        checksum::record(&copy, &blake3::hash(b"b")).unwrap();
+        assert_eq!(sorter.copy_in("edit", &picture), None);
+        checksum::record(&copy, &blake3::hash(b"a")).unwrap();
+        assert_eq!(sorter.copy_in("edit", &picture), Some(copy));
+    }
+
+    #[test]
+    fn shuffle_walks_a_permutation() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let pictures: Vec<PathBuf> = (0..20)
+            .map(|i| PathBuf::from(format!("{}.jpg", i)))
+            .collect();
+        let mut logic =
+            AppLogic::with_pictures(sorter(Path::new(".")), pictures.clone(), 6).unwrap();
+        runtime.block_on(async {
+            // letting the spawned loads run before reading an image
+            let yield_now = tokio::task::yield_now;
+            logic.init().await;
+            logic.buffer.go_to(5).await;
+            yield_now().await;
+            logic.get_img().await;
+            logic.shuffle().await;
+            yield_now().await;
+            let mut shown = vec![logic.get_img().await.path];
+            while logic.next_img().await {
+                yield_now().await;
+                shown.push(logic.get_img().await.path);
+            }
+            assert_eq!(shown[0], pictures[5]);
+            shown.sort();
+            let mut sorted = pictures.clone();
+            sorted.sort();
+            assert_eq!(shown, sorted);
+            logic.unshuffle().await;
+            yield_now().await;
+            assert_eq!(logic.buffer.pic_list(), pictures);
+            assert_eq!(logic.get_img().await.path, logic.current_path);
+        });
+    }
+
+    #[test]
+    fn pictures_read_from_a_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let other = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a.jpg"), other.path().join("b.JPG"));
+        for file in [
+            &a,
+            &b,
+            &dir.path().join("a.RAF"),
+            &dir.path().join("notes.txt"),
+        ] {
+            fs::write(file, "").unwrap();
+        }
+        let list = format!(
+            "{b}\n\n  {a}  \n{raf}\n{notes}\n{missing}\n{a}\n",
+            a = a.display(),
+            b = b.display(),
+            raf = dir.path().join("a.RAF").display(),
+            notes = dir.path().join("notes.txt").display(),
+            missing = dir.path().join("c.jpg").display(),
+        );
+        let pictures = read_pictures(list.as_bytes()).unwrap();
+        assert_eq!(pictures, [b.clone(), a]);
+
+        // each picture has its own edit folder, buckets stay in the browsed folder
+        let mut sorter = sorter(dir.path());
+        sorter.buckets.push("print:p:copy".parse().unwrap());
+        let [edit, _, print] = sorter.get_buckets() else {
+            panic!("edit, bin and print expected");
+        };
+        assert_eq!(sorter.send_picture(edit, &b).0.message, Message::SentNoRAW);
+        assert_eq!(sorter.send_picture(print, &b).0.message, Message::SentNoRAW);
+        assert!(other.path().join("edit/b.JPG").exists());
+        assert!(dir.path().join("print/b.JPG").exists());
     }
 }
@@ -1,11 +1,33 @@
+use std::cell::Cell;
 use std::error::Error;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::rc::Rc;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
-use clap::Parser;
+use clap::{CommandFactory, Parser, Subcommand};
 
-use phog::logic::{AppLogic, AppWindow, ImageStat};
-use slint::ComponentHandle;
+use phog::bucket::Bucket;
+use phog::checksum::{self, FileCheck};
+use phog::config::{Config, Interval, Theme};
+use phog::error::PhogError;
+use phog::export::{self, ExportFormat, Selection};
+use phog::ingest::Ingested;
+use phog::keymap::{KeyPress, Keymap};
+use phog::logic::{
+    list_pictures, read_pictures, AppLogic, AppWindow, BucketInfo, ColorTheme, ConflictPolicy,
+    DeleteMode, ImageStat, KeyAction, SortOrder, Sorter,
+};
+use phog::pick_list::PickList;
+use phog::rename::{RenamePattern, RenamePlan, DEFAULT_PATTERN};
+use phog::scan::{Scan, Shot};
+use phog::session::{PictureDecisions, Session, SESSION_NAME};
+use phog::template::FolderTemplate;
+use phog::transfer::LinkMode;
+use phog::watch::{watch_folder, FolderChange};
+use slint::{ComponentHandle, Model, ModelRc, VecModel};
 
 /// Syntactic sugar for async in slint callback
 ///
@@ -36,6 +58,22 @@ macro_rules! async_context {
             .unwrap();
         }
     }};
+    ($ui:ident, $logic:ident, $arg:ident: $arg_type:ty, $code:block) => {{
+        // same but with a callback param instead of repeat
+        let ui_handle = $ui.as_weak();
+        let logic_ref: Arc<Mutex<AppLogic>> = $logic.clone();
+        move |$arg: $arg_type| {
+            let logic_c = logic_ref.clone();
+            #[allow(unused)]
+            let $ui = ui_handle.unwrap();
+            slint::spawn_local(async_compat::Compat::new(async move {
+                #[allow(unused_mut)]
+                let mut $logic = logic_c.lock().await;
+                $code
+            }))
+            .unwrap();
+        }
+    }};
     ($ui:ident, $logic:ident, $code:block) => {{
         // same but not repeat param
         let ui_handle = $ui.as_weak();
@@ -54,10 +92,24 @@ macro_rules! async_context {
     }};
 }
 /// Gets an image and update all needed ui element for a new image
+/// (quits if all images were removed from the disk)
 macro_rules! update_image {
     ($ui:ident, $logic:ident) => {{
+        if !$logic.sync_current().await {
+            slint::quit_event_loop().unwrap();
+            return;
+        }
         let img: ImageStat = $logic.get_img().await;
         update_image_only!($ui, img);
+        update_decisions!($ui, $logic);
+    }};
+}
+
+/// Updates the decisions shown for the current image and the pending count
+macro_rules! update_decisions {
+    ($ui:ident, $logic:ident) => {{
+        $ui.set_photo_marks($logic.current_marks().into());
+        $ui.set_pending($logic.pending() as i32);
     }};
 }
 
@@ -68,44 +120,970 @@ macro_rules! update_image_only {
         $ui.set_photo_num($img.number as i32);
         $ui.set_total_num($img.out_of as i32);
         $ui.set_photo_name($img.name.into());
+        $ui.set_photo_error($img.error.map(|e| e.to_string()).unwrap_or_default().into());
     }};
 }
 
-/// args
+/// args, overriding the configuration files (~/.config/phog/config.toml and .phog.toml in the folder)
 #[derive(Parser)]
 struct Cli {
-    /// Delay between key presses when holding down a key
-    #[clap(short, long, value_name="MS", default_value_t = 600)]
-    delay_ms: u64,
+    #[clap(subcommand)]
+    command: Option<Command>,
+    /// - to browse the photos listed on standard input, like --stdin
+    #[clap(value_name = "-", value_parser = ["-"], conflicts_with = "watch")]
+    dash: Option<String>,
+    /// Delay between key presses when holding down a key [default: 600]
+    #[clap(short, long, value_name="MS")]
+    delay_ms: Option<u64>,
+    /// Folder photos are copied to, can be a path with ~ and {year}, {month}, {day}, {date}
+    /// or {camera} [default: edit]
+    #[clap(long, value_name = "FOLDER")]
+    edit_folder: Option<FolderTemplate>,
+    /// Folder photos are moved to when deleted, same format as --edit-folder [default: bin]
+    #[clap(long, value_name = "FOLDER")]
+    bin_folder: Option<FolderTemplate>,
+    /// How photos are put in the edit folder, links fall back to a copy when not possible
+    /// [default: copy]
+    #[clap(long, value_enum)]
+    edit_with: Option<LinkMode>,
+    /// Check copies to the edit folder and buckets against the original and record their
+    /// checksum
+    #[clap(long)]
+    checksum: bool,
+    /// Don't check copies, even if the configuration sets checksum
+    #[clap(long, overrides_with = "checksum")]
+    no_checksum: bool,
+    /// What to do when a different photo with the same name is already in the destination
+    /// [default: rename]
+    #[clap(long, value_enum)]
+    on_conflict: Option<ConflictPolicy>,
+    /// Where deleted photos go [default: folder]
+    #[clap(long, value_enum)]
+    delete_to: Option<DeleteMode>,
+    /// Extra destination for photos as NAME:KEY:MODE[:PATH], MODE being copy, move, link or
+    /// hardlink and PATH defaulting to a NAME subfolder (e.g. client:c:copy). Can be repeated
+    #[clap(short, long = "bucket", value_name = "BUCKET")]
+    buckets: Vec<Bucket>,
+    /// Extensions of the RAW files next to the photos, the first one found is used
+    /// [default: RAF]
+    #[clap(long = "raw-ext", value_name = "EXT", value_delimiter = ',')]
+    raw_extensions: Vec<String>,
+    /// Number of photos kept loaded around the current one [default: 8]
+    #[clap(long, value_name = "N")]
+    buffer_size: Option<usize>,
+    /// Order in which photos are browsed [default: name]
+    #[clap(long, value_enum)]
+    sort: Option<SortOrder>,
+    /// Colors of the app [default: system]
+    #[clap(long, value_enum)]
+    theme: Option<Theme>,
+    /// Show the photos fullscreen on a black background, without the file name (toggled with
+    /// F11 or f, the choice being remembered with save-changes)
+    #[clap(long)]
+    fullscreen: bool,
+    /// Don't start fullscreen, even if the configuration sets it
+    #[clap(long, overrides_with = "fullscreen")]
+    no_fullscreen: bool,
+    /// Only record what is decided for each photo, applied in one go after a review
+    /// (decisions are kept in .phog-session.json until then)
+    #[clap(long)]
+    defer: bool,
+    /// Only log what would be done to the disk (source → destination, bytes), the app
+    /// behaving as if it was done
+    #[clap(long, global = true)]
+    dry_run: bool,
+    /// With --dry-run, write the log to this file when quitting
+    #[clap(long, value_name = "FILE", requires = "dry_run", global = true)]
+    dry_run_log: Option<PathBuf>,
+    /// Mark the photos named in this list for the edit folder (file names with or without
+    /// folder and extension, or frame numbers, one per line or separated by commas). Switches to
+    /// --defer: the marks are reviewed and applied with Enter
+    #[clap(long, value_name = "FILE")]
+    select_from: Option<PathBuf>,
+    /// With --select-from, only browse the photos of the list
+    #[clap(long, requires = "select_from", conflicts_with = "watch")]
+    only_selected: bool,
+    /// With --select-from, copy the photos of the list to the edit folder without opening the app
+    #[clap(long, requires = "select_from")]
+    copy_selected: bool,
+    /// Start a slideshow showing each photo for this long (e.g. 4s or 500ms), any key pausing
+    /// it. Also sets the interval of the slideshows started with F5 [default: 4s]
+    #[clap(long, value_name = "INTERVAL")]
+    slideshow: Option<Interval>,
+    /// Start slideshows again from the first photo after the last one
+    #[clap(long)]
+    slideshow_loop: bool,
+    /// Show the photos of slideshows in random order
+    #[clap(long)]
+    slideshow_shuffle: bool,
+    /// Browse the photos listed on standard input, one path per line (e.g. from find or fd),
+    /// instead of the current folder. Their edit and bin folders are next to each of them
+    #[clap(long, conflicts_with = "watch")]
+    stdin: bool,
+    /// Write the edit and bin folders changed in the app to the configuration
+    #[clap(long)]
+    save_config: bool,
+    /// Watch the folder and add new photos as they are written (tethered shooting)
+    #[clap(short, long)]
+    watch: bool,
+    /// With --watch, jump to each new photo as soon as it arrives
+    #[clap(long, requires = "watch")]
+    follow: bool,
+}
+
+impl Cli {
+    /// Settings given on the command line
+    fn config(&mut self) -> Config {
+        Config {
+            edit_folder: self.edit_folder.take(),
+            bin_folder: self.bin_folder.take(),
+            edit_with: self.edit_with,
+            checksum: match (self.checksum, self.no_checksum) {
+                (_, true) => Some(false),
+                (checksum, false) => checksum.then_some(true),
+            },
+            on_conflict: self.on_conflict,
+            delete_to: self.delete_to,
+            buckets: (!self.buckets.is_empty()).then(|| std::mem::take(&mut self.buckets)),
+            raw_extensions: (!self.raw_extensions.is_empty())
+                .then(|| std::mem::take(&mut self.raw_extensions)),
+            buffer_size: self.buffer_size,
+            sort: self.sort,
+            delay_ms: self.delay_ms,
+            theme: self.theme,
+            defer: self.defer.then_some(true),
+            slideshow_interval: self.slideshow,
+            slideshow_loop: self.slideshow_loop.then_some(true),
+            slideshow_shuffle: self.slideshow_shuffle.then_some(true),
+            fullscreen: match (self.fullscreen, self.no_fullscreen) {
+                (_, true) => Some(false),
+                (fullscreen, false) => fullscreen.then_some(true),
+            },
+            // only in the configuration files
+            keys: None,
+            save_changes: self.save_config.then_some(true),
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Re-check the files of a folder against the checksums recorded with --checksum
+    Verify {
+        /// Folder to check
+        #[clap(default_value = "edit")]
+        folder: PathBuf,
+    },
+    /// Apply decisions taken with --defer (e.g. on another computer) without opening the app
+    Apply {
+        /// File of decisions, like the .phog-session.json of a folder
+        file: PathBuf,
+        /// Folder the decisions were taken in [default: folder of FILE]
+        #[clap(long, value_name = "FOLDER")]
+        folder: Option<PathBuf>,
+    },
+    /// List the photos of a folder with their RAW and sidecars, and the RAW files without a photo
+    Scan {
+        /// Folder to scan
+        #[clap(default_value = ".")]
+        folder: PathBuf,
+        /// Print the report as JSON
+        #[clap(long)]
+        json: bool,
+    },
+    /// Remove the RAW files whose photo is gone (to the bin folder, or the trash with
+    /// --delete-to trash), after listing them
+    CleanRaws {
+        /// Folder to clean up
+        #[clap(default_value = ".")]
+        folder: PathBuf,
+        /// Don't ask for confirmation
+        #[clap(short, long)]
+        yes: bool,
+    },
+    /// Copy the photos of a card and their RAW to LIBRARY/{year}/{date}/ (from the date they
+    /// were taken), check the copies and skip the files already imported, then open them
+    Ingest {
+        /// Card or folder to import, subfolders included
+        card: PathBuf,
+        /// Folder of the dated folders
+        library: PathBuf,
+        /// Only copy the photos, without opening them
+        #[clap(long)]
+        no_open: bool,
+    },
+    /// Rename the photos of a folder with their RAW and sidecars from a pattern of EXIF fields
+    /// ({year}, {month}, {day}, {date}, {hour}, {minute}, {second}, {time}, {camera}), the
+    /// original name ({stem}), its number ({frame}) and a counter ({n}, {n:4} zero padded),
+    /// e.g. {date}_{time}_{frame}. The new names are listed first
+    Rename {
+        pattern: RenamePattern,
+        /// Folder of the photos
+        #[clap(default_value = ".")]
+        folder: PathBuf,
+        /// First value of the counter
+        #[clap(long, value_name = "N", default_value_t = 1)]
+        start: usize,
+        /// Don't ask for confirmation
+        #[clap(short, long)]
+        yes: bool,
+    },
+    /// Give back their names to the photos of a folder renamed last, in the app or with rename
+    UndoRename {
+        /// Folder of the photos
+        #[clap(default_value = ".")]
+        folder: PathBuf,
+    },
+    /// List the photos copied to the edit folder or a bucket, or rated or labeled in an editor
+    /// (XMP sidecars), with their RAW and sidecars [default: --in edit]
+    Export {
+        /// Folder of the photos
+        #[clap(default_value = ".")]
+        folder: PathBuf,
+        /// Photos copied, or marked to be with --defer, to the edit folder (edit) or to the bucket
+        /// with this name
+        #[clap(long = "in", value_name = "DESTINATION")]
+        copied_to: Option<String>,
+        /// Photos with at least this many stars
+        #[clap(long, value_name = "N")]
+        min_rating: Option<i32>,
+        /// Photos with this color label
+        #[clap(long)]
+        label: Option<String>,
+        #[clap(long, value_enum, default_value = "txt")]
+        format: ExportFormat,
+        /// File to write the list to [default: standard output]
+        #[clap(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+}
+
+/// Checks a folder against its manifest, listing files that don't match
+fn verify(folder: PathBuf) -> Result<ExitCode, Box<dyn Error>> {
+    let checks = checksum::verify_folder(&folder)?;
+    let mut failed = 0;
+    for (name, check) in checks.iter() {
+        match check {
+            FileCheck::Ok => continue,
+            FileCheck::Corrupted => println!("CORRUPTED {}", name),
+            FileCheck::Missing => println!("MISSING   {}", name),
+        }
+        failed += 1;
+    }
+    println!("{} files checked, {} failed", checks.len(), failed);
+    Ok(if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+/// Applies a file of decisions to the pictures of folder, listing the ones that failed.
+/// The file keeps the failed decisions so they can be tried again
+fn apply(
+    file: PathBuf,
+    folder: Option<PathBuf>,
+    cli_config: Config,
+    args: &Cli,
+) -> Result<ExitCode, Box<dyn Error>> {
+    let session = Session::load(&file)?;
+    let folder = match folder {
+        Some(folder) => folder,
+        None => file
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .map_or_else(|| PathBuf::from("."), Path::to_path_buf),
+    };
+    let folder = std::path::absolute(folder)?;
+    let config = cli_config.or(Config::load(&folder)?);
+    let sorter = sorter(folder, &config, args.dry_run)?;
+    let outcome = sorter.apply(session);
+    for failed in outcome.failed.iter() {
+        println!("FAILED {}", failed);
+    }
+    println!(
+        "{} decisions applied, {} failed",
+        outcome.applied,
+        outcome.failed.len()
+    );
+    if !args.dry_run {
+        outcome.remaining.save(&file)?;
+    }
+    export_dry_run(&sorter, args.dry_run_log.as_deref())?;
+    Ok(if outcome.failed.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+/// Reports what the app would see in a folder
+fn scan(folder: PathBuf, json: bool, cli_config: Config) -> Result<ExitCode, Box<dyn Error>> {
+    let folder = std::path::absolute(folder)?;
+    let config = cli_config.or(Config::load(&folder)?);
+    let sorter = sorter(folder, &config, false)?;
+    let scan = Scan::new(&sorter, config.sort.unwrap_or(SortOrder::Name))?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&scan)?);
+        return Ok(ExitCode::SUCCESS);
+    }
+    let picture_width = scan
+        .shots
+        .iter()
+        .map(|shot| shot.picture.chars().count())
+        .fold("PHOTO".len(), usize::max);
+    let raw_width = scan
+        .shots
+        .iter()
+        .map(|shot| shot.raw.as_deref().unwrap_or("-").chars().count())
+        .fold("RAW".len(), usize::max);
+    println!("{:picture_width$}  {:raw_width$}  SIDECARS", "PHOTO", "RAW");
+    for shot in scan.shots.iter() {
+        let sidecars = match shot.sidecars.is_empty() {
+            true => "-".into(),
+            false => shot.sidecars.join(", "),
+        };
+        println!(
+            "{:picture_width$}  {:raw_width$}  {}",
+            shot.picture,
+            shot.raw.as_deref().unwrap_or("-"),
+            sidecars
+        );
+    }
+    if !scan.orphan_raws.is_empty() {
+        println!("\nRAW files without a photo:");
+        for raw in scan.orphan_raws.iter() {
+            println!("{}", raw);
+        }
+    }
+    let jpeg_only = scan.jpeg_only().count();
+    println!(
+        "\n{} photos: {} with RAW, {} without; {} RAW without a photo",
+        scan.shots.len(),
+        scan.shots.len() - jpeg_only,
+        jpeg_only,
+        scan.orphan_raws.len()
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Removes the RAW files of a folder whose photo is gone, once confirmed
+fn clean_raws(
+    folder: PathBuf,
+    yes: bool,
+    cli_config: Config,
+    args: &Cli,
+) -> Result<ExitCode, Box<dyn Error>> {
+    let folder = std::path::absolute(folder)?;
+    let config = cli_config.or(Config::load(&folder)?);
+    let sorter = sorter(folder, &config, args.dry_run)?;
+    let raws = sorter.orphan_raws()?;
+    if raws.is_empty() {
+        println!("No RAW file without a photo");
+        return Ok(ExitCode::SUCCESS);
+    }
+    println!("RAW files without a photo:");
+    for raw in raws.iter() {
+        println!("{}", raw.display());
+    }
+    if !yes {
+        match sorter.delete_destination() {
+            Some(folder) => print!("Move these {} files to {}? [y/N] ", raws.len(), folder),
+            None => print!("Send these {} files to the trash? [y/N] ", raws.len()),
+        }
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+            println!("Nothing removed");
+            return Ok(ExitCode::SUCCESS);
+        }
+    }
+    let failed = sorter.remove_orphan_raws(&raws);
+    for raw in failed.iter() {
+        println!("FAILED {}", raw.display());
+    }
+    println!(
+        "{} RAW files removed, {} failed",
+        raws.len() - failed.len(),
+        failed.len()
+    );
+    export_dry_run(&sorter, args.dry_run_log.as_deref())?;
+    Ok(if failed.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+/// Copies a card to the library, listing the files that failed
+fn ingest(
+    card: &Path,
+    library: &Path,
+    cli_config: &Config,
+    args: &Cli,
+) -> Result<Ingested, Box<dyn Error>> {
+    let library = std::path::absolute(library)?;
+    let config = cli_config.clone().or(Config::load(&library)?);
+    let sorter = sorter(std::path::absolute(card)?, &config, args.dry_run)?;
+    let ingested = phog::ingest::ingest(&sorter, &library)?;
+    for failed in ingested.failed.iter() {
+        println!("FAILED {}", failed);
+    }
+    println!(
+        "{} files imported, {} already in the library, {} failed",
+        ingested.copied,
+        ingested.skipped,
+        ingested.failed.len()
+    );
+    if ingested.orphan_raws > 0 {
+        println!(
+            "{} RAW files without a photo left on the card",
+            ingested.orphan_raws
+        );
+    }
+    export_dry_run(&sorter, args.dry_run_log.as_deref())?;
+    Ok(ingested)
+}
+
+/// Opens the imported photos: their folder if they were taken on the same day, else the
+/// library with only them. Returns the photos to browse instead of the folder, if any
+fn open_ingested(pictures: Vec<PathBuf>, library: &Path) -> io::Result<Option<Vec<PathBuf>>> {
+    let mut folders: Vec<&Path> = pictures.iter().filter_map(|p| p.parent()).collect();
+    folders.sort();
+    folders.dedup();
+    if let [folder] = folders[..] {
+        std::env::set_current_dir(folder)?;
+        return Ok(None);
+    }
+    std::env::set_current_dir(library)?;
+    Ok(Some(pictures))
+}
+
+/// Renames the photos of a folder once the new names were listed and confirmed
+fn rename(
+    pattern: RenamePattern,
+    folder: PathBuf,
+    start: usize,
+    yes: bool,
+    cli_config: Config,
+    args: &Cli,
+) -> Result<ExitCode, Box<dyn Error>> {
+    let folder = std::path::absolute(folder)?;
+    let config = cli_config.or(Config::load(&folder)?);
+    let sorter = sorter(folder, &config, args.dry_run)?;
+    let pictures = list_pictures(sorter.get_folder(), config.sort.unwrap_or(SortOrder::Name))?;
+    let plan = RenamePlan::new(&sorter, &pictures, &pattern, start);
+    if plan.renames.is_empty() && plan.errors.is_empty() {
+        println!("No photo to rename");
+        return Ok(ExitCode::SUCCESS);
+    }
+    let name = |path: &Path| {
+        path.file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned()
+    };
+    for (file, dest) in plan.files() {
+        println!("{} → {}", name(file), name(dest));
+    }
+    if !plan.errors.is_empty() {
+        for error in plan.errors.iter() {
+            println!("CAN'T RENAME {}", error);
+        }
+        println!(
+            "{} files can't be renamed, nothing renamed",
+            plan.errors.len()
+        );
+        return Ok(ExitCode::FAILURE);
+    }
+    if !yes {
+        print!(
+            "Rename these {} photos ({} files)? [y/N] ",
+            plan.renames.len(),
+            plan.file_count()
+        );
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+            println!("Nothing renamed");
+            return Ok(ExitCode::SUCCESS);
+        }
+    }
+    if let Err(err) = plan.apply(&sorter) {
+        println!("Renaming failed, nothing renamed: {}", err);
+        return Ok(ExitCode::FAILURE);
+    }
+    println!(
+        "{} photos renamed ({} files)",
+        plan.renames.len(),
+        plan.file_count()
+    );
+    export_dry_run(&sorter, args.dry_run_log.as_deref())?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Renames back the photos of a folder renamed last
+fn undo_rename(
+    folder: PathBuf,
+    cli_config: Config,
+    args: &Cli,
+) -> Result<ExitCode, Box<dyn Error>> {
+    let folder = std::path::absolute(folder)?;
+    let config = cli_config.or(Config::load(&folder)?);
+    let sorter = sorter(folder, &config, args.dry_run)?;
+    match RenamePlan::undo_last(&sorter) {
+        Ok(Some(plan)) => println!(
+            "{} photos got their names back ({} files)",
+            plan.renames.len(),
+            plan.file_count()
+        ),
+        Ok(None) => println!("No renaming to undo"),
+        Err(err) => {
+            println!("Undoing failed, nothing renamed: {}", err);
+            return Ok(ExitCode::FAILURE);
+        }
+    }
+    export_dry_run(&sorter, args.dry_run_log.as_deref())?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Writes the list of the selected photos of a folder
+fn export(
+    folder: PathBuf,
+    mut selection: Selection,
+    format: ExportFormat,
+    output: Option<PathBuf>,
+    cli_config: Config,
+) -> Result<ExitCode, Box<dyn Error>> {
+    let folder = std::path::absolute(folder)?;
+    let config = cli_config.or(Config::load(&folder)?);
+    let sorter = sorter(folder.clone(), &config, false)?;
+    if selection.copied_to.is_none() && selection.min_rating.is_none() && selection.label.is_none()
+    {
+        selection.copied_to = Some("edit".into());
+    }
+    if let Some(destination) = &selection.copied_to {
+        let known = sorter
+            .get_buckets()
+            .iter()
+            .any(|bucket| bucket.name == *destination);
+        if !known {
+            Cli::command()
+                .error(
+                    clap::error::ErrorKind::InvalidValue,
+                    format!("'{}' is neither edit, bin nor a bucket", destination),
+                )
+                .exit();
+        }
+    }
+    let scan = Scan::new(&sorter, config.sort.unwrap_or(SortOrder::Name))?;
+    let session = Session::load(&folder.join(SESSION_NAME))?;
+    // paths from the current folder when the photos are inside it, absolute else
+    let current = std::env::current_dir()?;
+    let base = folder.strip_prefix(&current).unwrap_or(&folder);
+    let shots: Vec<Shot> = selection
+        .select(&scan, &sorter, &session)
+        .iter()
+        .map(|shot| shot.in_folder(base))
+        .collect();
+    match &output {
+        Some(path) => {
+            let mut file = io::BufWriter::new(std::fs::File::create(path)?);
+            export::write(&shots, format, &mut file)?;
+            file.flush()?;
+            eprintln!("{} photos written to {}", shots.len(), path.display());
+        }
+        None => export::write(&shots, format, &mut io::stdout().lock())?,
+    }
+    Ok(ExitCode::SUCCESS)
 }
+
+/// Photos chosen in a list, warning about the entries matching none of them
+fn picked(list: &Path, pictures: &[PathBuf]) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let pick_list = PickList::load(list)?;
+    for entry in pick_list.unmatched(pictures) {
+        eprintln!("No photo matches {}", entry);
+    }
+    let picked = pick_list.select(pictures);
+    if picked.is_empty() {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::InvalidValue,
+                format!("no photo of {} found", list.display()),
+            )
+            .exit();
+    }
+    Ok(picked)
+}
+
+/// Puts the photos chosen in a list in the edit folder without opening the app
+fn copy_selected(
+    folder: PathBuf,
+    pictures: &[PathBuf],
+    config: &Config,
+    args: &Cli,
+) -> Result<ExitCode, Box<dyn Error>> {
+    let sorter = sorter(folder, config, args.dry_run)?;
+    let session = Session {
+        pictures: pictures
+            .iter()
+            .map(|picture| PictureDecisions {
+                file: sorter.session_file(picture),
+                buckets: vec!["edit".into()],
+            })
+            .collect(),
+    };
+    let outcome = sorter.apply(session);
+    for failed in outcome.failed.iter() {
+        println!("FAILED {}", failed);
+    }
+    println!(
+        "{} photos put in the edit folder, {} failed",
+        outcome.applied,
+        outcome.failed.len()
+    );
+    export_dry_run(&sorter, args.dry_run_log.as_deref())?;
+    Ok(if outcome.failed.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+/// Sends the pictures of folder to the buckets with the settings of config
+fn sorter(folder: PathBuf, config: &Config, dry_run: bool) -> Result<Sorter, Box<dyn Error>> {
+    let mut sorter = Sorter::new(
+        folder,
+        config
+            .edit_folder
+            .clone()
+            .map_or_else(|| "edit".parse(), Ok)?,
+        config
+            .bin_folder
+            .clone()
+            .map_or_else(|| "bin".parse(), Ok)?,
+        config.buckets.clone().unwrap_or_default(),
+    );
+    sorter.set_edit_mode(config.edit_with.unwrap_or(LinkMode::Copy));
+    sorter.set_delete_mode(config.delete_to.unwrap_or(DeleteMode::Folder));
+    sorter.set_conflict_policy(config.on_conflict.unwrap_or(ConflictPolicy::Rename));
+    sorter.set_verify_copies(config.checksum.unwrap_or(false));
+    if let Some(extensions) = config.raw_extensions.clone() {
+        sorter.set_raw_extensions(extensions);
+    }
+    if dry_run {
+        sorter.set_dry_run();
+    }
+    Ok(sorter)
+}
+
+/// Sums up a dry run, writing its log to a file if asked
+fn export_dry_run(sorter: &Sorter, log: Option<&Path>) -> io::Result<()> {
+    let Some(dry_run) = sorter.get_dry_run() else {
+        return Ok(());
+    };
+    println!(
+        "Dry run: {} operations not done",
+        dry_run.operations().len()
+    );
+    if let Some(path) = log {
+        dry_run.export(path)?;
+        println!("Log written to {}", path.display());
+    }
+    Ok(())
+}
+
+/// What the UI shows of a bucket
+fn bucket_info(bucket: &Bucket) -> BucketInfo {
+    BucketInfo {
+        key: KeyPress::from(bucket.key.as_str()).to_string().into(),
+        name: bucket.name.clone().into(),
+        mode: bucket.mode,
+    }
+}
+
+/// Shows the window until a file is written to the watched folder.
+/// Returns false if the window was closed first
+fn wait_for_files(ui: &AppWindow, folder: &Path) -> Result<bool, Box<dyn Error>> {
+    let (watcher, mut changes) = watch_folder(folder)?;
+    let arrived = Rc::new(Cell::new(false));
+    let arrived_c = arrived.clone();
+    slint::spawn_local(async_compat::Compat::new(async move {
+        let _watcher = watcher;
+        while let Some(change) = changes.recv().await {
+            if let FolderChange::Added(_) = change {
+                arrived_c.set(true);
+                slint::quit_event_loop().unwrap();
+                return;
+            }
+        }
+    }))?;
+    // the window stays shown for the browsing that follows
+    ui.show()?;
+    slint::run_event_loop()?;
+    Ok(arrived.get())
+}
+
 // TODO add a start from function
 // TODO add a regex match function
 // TODO add a license
 // no #[tokio::main] because it crashes after a few Mutex locks (compatibility issue with slint)
-fn main() -> Result<(), Box<dyn Error>> {
+fn main() -> Result<ExitCode, Box<dyn Error>> {
     // Attempts to find locale translation (default English)
     slint::init_translations!(concat!(env!("CARGO_MANIFEST_DIR"), "/lang/"));
-    let args = Cli::parse();
-    let delay = Duration::from_millis(args.delay_ms);
+    let mut args = Cli::parse();
+    let command = args.command.take();
+    if let Some(Command::Verify { folder }) = command {
+        return verify(folder);
+    }
+    let cli_config = args.config();
+    if let Err(msg) = cli_config.validate() {
+        Cli::command()
+            .error(clap::error::ErrorKind::ValueValidation, msg)
+            .exit();
+    }
+    // photos just imported, when not all in the same folder
+    let mut ingested = None;
+    match command {
+        Some(Command::Ingest {
+            card,
+            library,
+            no_open,
+        }) => {
+            let imported = ingest(&card, &library, &cli_config, &args)?;
+            if !imported.failed.is_empty() {
+                return Ok(ExitCode::FAILURE);
+            }
+            // nothing was copied in a dry run
+            if no_open || args.dry_run || imported.pictures.is_empty() {
+                return Ok(ExitCode::SUCCESS);
+            }
+            ingested = open_ingested(imported.pictures, &library)?;
+        }
+        Some(Command::Apply { file, folder }) => return apply(file, folder, cli_config, &args),
+        Some(Command::Scan { folder, json }) => return scan(folder, json, cli_config),
+        Some(Command::CleanRaws { folder, yes }) => {
+            return clean_raws(folder, yes, cli_config, &args)
+        }
+        Some(Command::Rename {
+            pattern,
+            folder,
+            start,
+            yes,
+        }) => return rename(pattern, folder, start, yes, cli_config, &args),
+        Some(Command::UndoRename { folder }) => return undo_rename(folder, cli_config, &args),
+        Some(Command::Export {
+            folder,
+            copied_to,
+            min_rating,
+            label,
+            format,
+            output,
+        }) => {
+            let selection = Selection {
+                copied_to,
+                min_rating,
+                label,
+            };
+            return export(folder, selection, format, output, cli_config);
+        }
+        _ => (),
+    }
+    let folder_path = std::env::current_dir()?;
+    let config = cli_config.or(Config::load(&folder_path)?);
+    let sort = config.sort.unwrap_or(SortOrder::Name);
+    // photos given on standard input, in their order, instead of the ones of the folder
+    let listed = match args.stdin || args.dash.is_some() {
+        true => Some(read_pictures(io::stdin().lock())?),
+        false => ingested,
+    };
+    let picked = match &args.select_from {
+        Some(list) => Some(match &listed {
+            Some(listed) => picked(list, listed)?,
+            None => picked(list, &list_pictures(&folder_path, sort)?)?,
+        }),
+        None => None,
+    };
+    if args.copy_selected {
+        return copy_selected(folder_path, &picked.unwrap_or_default(), &config, &args);
+    }
+    let buckets = config.buckets.as_deref().unwrap_or_default();
+    // buckets keep their key over the default ones of phog
+    let bucket_keys: Vec<_> = buckets
+        .iter()
+        .map(|bucket| KeyPress::from(bucket.key.as_str()))
+        .collect();
+    let keymap = Keymap::new(&config.keys.clone().unwrap_or_default(), &bucket_keys)?;
+    for (i, bucket) in buckets.iter().enumerate() {
+        let msg = if buckets[..i].iter().any(|other| other.key == bucket.key) {
+            format!("key '{}' is used by more than one bucket", bucket.key)
+        } else if keymap.is_used(&KeyPress::from(bucket.key.as_str())) {
+            format!("key '{}' is already used by phog", bucket.key)
+        } else {
+            continue;
+        };
+        Cli::command()
+            .error(clap::error::ErrorKind::ArgumentConflict, msg)
+            .exit();
+    }
+    // nothing is written in a dry run, configuration included
+    let save_changes = config.save_changes.unwrap_or(false) && !args.dry_run;
+    let delay = Duration::from_millis(config.delay_ms.unwrap_or(600));
     let ui = AppWindow::new()?;
+    let fullscreen = config.fullscreen.unwrap_or(false);
     ui.window().set_maximized(true);
-    let folder_path = std::env::current_dir()?;
-    let logic = Arc::new(Mutex::new(AppLogic::new(
-        folder_path,
-        "edit".into(),
-        "bin".into(),
-    )));
-    let first: ImageStat = logic.blocking_lock().get_first_img();
-    update_image_only!(ui, first);
+    ui.window().set_fullscreen(fullscreen);
+    ui.set_fullscreen(fullscreen);
+    ui.set_theme(match config.theme.unwrap_or(Theme::System) {
+        Theme::System => ColorTheme::System,
+        Theme::Light => ColorTheme::Light,
+        Theme::Dark => ColorTheme::Dark,
+    });
+    let (edit_key, delete_key) = (
+        keymap.edit_key().to_string(),
+        keymap.delete_key().to_string(),
+    );
+    // photos picked from a list are marks to review
+    let defer = config.defer.unwrap_or(false) || picked.is_some();
+    let mut key_help = keymap.help();
+    if !defer {
+        key_help.retain(|binding| binding.action != KeyAction::Review);
+    }
+    if let Some(binding) = key_help
+        .iter()
+        .find(|binding| binding.action == KeyAction::Review)
+    {
+        ui.set_review_keys(binding.keys.clone());
+    }
+    ui.set_deferred(defer);
+    ui.set_rename_pattern(DEFAULT_PATTERN.into());
+    let slideshow_interval = config
+        .slideshow_interval
+        .map_or(Duration::from_secs(4), |interval| interval.0);
+    ui.set_slideshow_interval(slideshow_interval.as_millis() as i64);
+    let slideshow_loop = config.slideshow_loop.unwrap_or(false);
+    let slideshow_shuffle = config.slideshow_shuffle.unwrap_or(false);
+    ui.set_dry_run(args.dry_run);
+    ui.set_key_help(ModelRc::new(VecModel::from(key_help)));
+    ui.on_key_action(move |text, alt, control| {
+        keymap.action(&KeyPress {
+            text: text.into(),
+            alt,
+            control,
+        })
+    });
+    // only tried again when watching an empty folder, once files arrived
+    let logic = loop {
+        let mut sorter = sorter(folder_path.clone(), &config, args.dry_run)?;
+        sorter.set_edit_key(&edit_key);
+        sorter.set_delete_key(&delete_key);
+        let buffer_size = config.buffer_size.unwrap_or(phog::logic::BUFFER_SIZE);
+        let logic = match (&picked, &listed) {
+            (Some(picked), _) if args.only_selected => {
+                AppLogic::with_pictures(sorter, picked.clone(), buffer_size)
+            }
+            (_, Some(listed)) => AppLogic::with_pictures(sorter, listed.clone(), buffer_size),
+            _ => AppLogic::new(sorter, sort, buffer_size),
+        };
+        match logic {
+            Ok(mut logic) => {
+                if defer {
+                    logic.defer_decisions()?;
+                }
+                if let Some(picked) = &picked {
+                    let marked = logic.pick(picked);
+                    println!(
+                        "{} photos of the list, {} newly marked for the edit folder \
+                        (deferred: press Enter to review and apply)",
+                        picked.len(),
+                        marked
+                    );
+                }
+                let buckets: Vec<BucketInfo> = logic
+                    .sorter()
+                    .get_buckets()
+                    .iter()
+                    .map(bucket_info)
+                    .collect();
+                ui.set_buckets(ModelRc::new(VecModel::from(buckets)));
+                break Arc::new(Mutex::new(logic));
+            }
+            Err(PhogError::EmptyFolder) => {
+                // nothing to browse, only showing a message until closed (or photos arrive)
+                ui.set_empty_folder(true);
+                ui.set_folder_name(folder_path.display().to_string().into());
+                ui.on_close(|| {
+                    slint::quit_event_loop().unwrap();
+                });
+                if !args.watch {
+                    ui.run()?;
+                    return Ok(ExitCode::SUCCESS);
+                }
+                if !wait_for_files(&ui, &folder_path)? {
+                    return Ok(ExitCode::SUCCESS);
+                }
+                ui.set_empty_folder(false);
+            }
+            Err(err) => return Err(err.into()),
+        }
+    };
+    {
+        let mut logic = logic.blocking_lock();
+        let first: ImageStat = logic.get_first_img();
+        update_image_only!(ui, first);
+        update_decisions!(ui, logic);
+    }
+    ui.set_slideshow(args.slideshow.is_some());
     let mut last_cmd = Instant::now();
 
     let logic_c = logic.clone();
+    let shuffled = args.slideshow.is_some() && slideshow_shuffle;
     slint::spawn_local(async_compat::Compat::new(async move {
         let mut logic = logic_c.lock().await;
-        logic.init().await;
+        // the buffer is loaded in the order of the slideshow
+        match shuffled {
+            true => logic.shuffle().await,
+            false => logic.init().await,
+        }
     }))
     .unwrap();
 
+    if args.watch {
+        let (watcher, mut changes) = watch_folder(&folder_path)?;
+        let ui_handle = ui.as_weak();
+        let logic_c = logic.clone();
+        slint::spawn_local(async_compat::Compat::new(async move {
+            // watching as long as changes are listened to
+            let _watcher = watcher;
+            while let Some(change) = changes.recv().await {
+                let ui = ui_handle.unwrap();
+                let mut logic = logic_c.lock().await;
+                match change {
+                    FolderChange::Added(path) => {
+                        if logic.add_image(path).await && args.follow {
+                            logic.go_to_last().await;
+                        }
+                    }
+                    FolderChange::Removed(path) => {
+                        if !logic.remove_image(&path).await {
+                            slint::quit_event_loop().unwrap();
+                            return;
+                        }
+                    }
+                }
+                // files rewritten in place are reloaded
+                if !logic.sync().await {
+                    slint::quit_event_loop().unwrap();
+                    return;
+                }
+                update_image!(ui, logic);
+            }
+        }))
+        .unwrap();
+    }
+
     ui.on_next(async_context! {delay, last_cmd, ui, logic, {
         if logic.next_img().await{
             update_image!(ui, logic);
@@ -117,13 +1095,79 @@ fn main() -> Result<(), Box<dyn Error>> {
             update_image!(ui, logic);
         }
     }});
-    ui.on_edit(async_context! {ui, logic, {
-        ui.invoke_display_message(logic.edit());
+
+    ui.on_start_slideshow(async_context! {ui, logic, {
+        if slideshow_shuffle {
+            logic.shuffle().await;
+            update_image!(ui, logic);
+        }
+        ui.set_slideshow(true);
+    }});
+    ui.on_slideshow_next(async_context! {ui, logic, {
+        // shuffled photos are browsed in their random order, shuffled again to loop
+        let moved = if logic.next_img().await {
+            true
+        } else if slideshow_loop && slideshow_shuffle {
+            logic.shuffle().await;
+            logic.next_img().await
+        } else if slideshow_loop {
+            logic.go_to_first().await;
+            true
+        } else {
+            false
+        };
+        if !moved {
+            ui.invoke_stop_slideshow(true);
+            return;
+        }
+        update_image!(ui, logic);
+    }});
+    ui.on_slideshow_stopped(async_context! {ui, logic, {
+        logic.unshuffle().await;
+        update_image!(ui, logic);
+    }});
+    let ui_handle = ui.as_weak();
+    ui.on_toggle_fullscreen(move || {
+        let ui = ui_handle.unwrap();
+        let fullscreen = !ui.get_fullscreen();
+        ui.window().set_fullscreen(fullscreen);
+        ui.set_fullscreen(fullscreen);
+        // a preference of the user rather than of the folder
+        if save_changes {
+            if let Err(err) = Config::save_user("fullscreen", fullscreen) {
+                println!("{}", err);
+            }
+        }
+    });
+
+    ui.on_bucket(async_context! {ui, logic, key: slint::SharedString, {
+        let Some((report, bucket, to_update)) = logic.send_to_bucket(&key).await else {
+            return;
+        };
+        // the mode used is shown, a link may have fallen back to a copy
+        let info = BucketInfo { mode: report.mode, ..bucket_info(&bucket) };
+        ui.invoke_display_conflict(report.conflict, report.new_name.into());
+        ui.invoke_display_message(report.message, info);
+        if to_update {
+            update_image!(ui, logic);
+        }
+        else {
+            slint::quit_event_loop().unwrap();
+        }
+    }});
+
+    ui.on_review(async_context! {ui, logic, {
+        let (summary, decisions) = logic.review();
+        let decisions: Vec<slint::SharedString> =
+            decisions.into_iter().map(Into::into).collect();
+        ui.set_review_summary(ModelRc::new(VecModel::from(summary)));
+        ui.set_review_list(ModelRc::new(VecModel::from(decisions)));
+        ui.invoke_display_review();
     }});
 
-    ui.on_delete(async_context! {ui, logic, {
-        let (status, to_update) = logic.delete().await;
-        ui.invoke_display_message(status);
+    ui.on_apply(async_context! {ui, logic, {
+        let (applied, failed, to_update) = logic.apply().await;
+        ui.invoke_display_applied(applied as i32, failed as i32);
         if to_update {
             update_image!(ui, logic);
         }
@@ -132,17 +1176,130 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
     }});
 
+    ui.on_find_orphans(async_context! {ui, logic, {
+        let sorter = logic.sorter();
+        let raws: Vec<slint::SharedString> = match sorter.orphan_raws() {
+            Ok(raws) => raws
+                .iter()
+                .filter_map(|raw| raw.file_name())
+                .map(|name| name.to_string_lossy().as_ref().into())
+                .collect(),
+            Err(err) => {
+                println!("{}", err);
+                return;
+            }
+        };
+        ui.set_orphan_raws(ModelRc::new(VecModel::from(raws)));
+        ui.set_orphans_destination(sorter.delete_destination().unwrap_or_default().into());
+        ui.invoke_display_orphans();
+    }});
+
+    ui.on_remove_orphans(async_context! {ui, logic, {
+        // the files that were confirmed, even if the folder changed since
+        let sorter = logic.sorter();
+        let raws: Vec<PathBuf> = ui
+            .get_orphan_raws()
+            .iter()
+            .map(|name| sorter.get_folder().join(name.as_str()))
+            .collect();
+        let failed = sorter.remove_orphan_raws(&raws);
+        ui.invoke_display_orphans_removed((raws.len() - failed.len()) as i32, failed.len() as i32);
+    }});
+
     ui.on_prep_bin_input(async_context! {ui, logic, {
-        ui.invoke_display_text_input(logic.get_delete_folder().into());
+        ui.invoke_display_text_input(logic.sorter_mut().get_delete_folder().into());
     }});
     ui.on_prep_edit_input(async_context! {ui, logic, {
-        ui.invoke_display_text_input(logic.get_edit_folder().into());
+        ui.invoke_display_text_input(logic.sorter_mut().get_edit_folder().into());
     }});
     ui.on_set_bin_input(async_context! {ui, logic, {
-        logic.set_delete_folder(ui.get_text_input().into());
+        let text = ui.get_text_input();
+        if let Err(err) = logic.sorter_mut().set_delete_folder(&text) {
+            // prompt stays open until the folder is valid
+            ui.invoke_display_text_input(text);
+            ui.invoke_display_input_preview(err.into(), false);
+        } else if save_changes {
+            if let Err(err) = Config::save(logic.sorter().get_folder(), "bin-folder", &text) {
+                println!("{}", err);
+            }
+        }
     }});
     ui.on_set_edit_input(async_context! {ui, logic, {
-        logic.set_edit_folder(ui.get_text_input().into());
+        let text = ui.get_text_input();
+        if let Err(err) = logic.sorter_mut().set_edit_folder(&text) {
+            ui.invoke_display_text_input(text);
+            ui.invoke_display_input_preview(err.into(), false);
+        } else if save_changes {
+            if let Err(err) = Config::save(logic.sorter().get_folder(), "edit-folder", &text) {
+                println!("{}", err);
+            }
+        }
+    }});
+    ui.on_preview_input(async_context! {ui, logic, text: slint::SharedString, {
+        match logic.preview_folder(&text) {
+            Ok(folder) => ui.invoke_display_input_preview(folder.into(), true),
+            Err(err) => ui.invoke_display_input_preview(err.into(), false),
+        }
+    }});
+
+    ui.on_preview_rename(async_context! {ui, logic, text: slint::SharedString, {
+        match logic.preview_rename(&text) {
+            Ok(preview) => ui.invoke_display_input_preview(preview.into(), true),
+            Err(err) => ui.invoke_display_input_preview(err.into(), false),
+        }
+    }});
+    ui.on_plan_rename(async_context! {ui, logic, {
+        let text = ui.get_text_input();
+        let pattern: RenamePattern = match text.parse() {
+            Ok(pattern) => pattern,
+            Err(err) => {
+                // prompt stays open until the pattern is valid
+                ui.invoke_display_text_input(text);
+                ui.invoke_display_input_preview(err.into(), false);
+                return;
+            }
+        };
+        ui.set_rename_pattern(text);
+        let plan = logic.plan_rename(&pattern);
+        if let Some(error) = plan.errors.first() {
+            for error in plan.errors.iter() {
+                println!("{}", error);
+            }
+            ui.invoke_display_rename_failed(plan.errors.len() as i32, error.into());
+            return;
+        }
+        if plan.renames.is_empty() {
+            ui.invoke_display_renamed(0);
+            return;
+        }
+        let name = |path: &Path| path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let renames: Vec<slint::SharedString> = plan
+            .files()
+            .map(|(file, dest)| format!("{} → {}", name(file), name(dest)).into())
+            .collect();
+        ui.set_rename_count(plan.renames.len() as i32);
+        ui.set_rename_list(ModelRc::new(VecModel::from(renames)));
+        ui.invoke_display_rename_plan();
+    }});
+    ui.on_rename(async_context! {ui, logic, {
+        match logic.rename().await {
+            Ok(renamed) => ui.invoke_display_renamed(renamed as i32),
+            Err(err) => {
+                ui.invoke_display_rename_failed(0, err.to_string().into());
+                return;
+            }
+        }
+        update_image!(ui, logic);
+    }});
+    ui.on_undo_rename(async_context! {ui, logic, {
+        match logic.undo_rename().await {
+            Ok(renamed) => ui.invoke_display_rename_undone(renamed as i32),
+            Err(err) => {
+                ui.invoke_display_rename_failed(0, err.to_string().into());
+                return;
+            }
+        }
+        update_image!(ui, logic);
     }});
 
     ui.on_close(|| {
@@ -151,5 +1308,6 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     ui.run()?;
 
-    Ok(())
+    export_dry_run(logic.blocking_lock().sorter(), args.dry_run_log.as_deref())?;
+    Ok(ExitCode::SUCCESS)
 }
@@ -0,0 +1,140 @@
+use crate::error::PhogError;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Photos chosen outside the app (e.g. by a client), as file names or frame numbers.
+/// Entries are matched whatever their folder, extension (photo or RAW) and case
+#[derive(Debug, Clone, Default)]
+pub struct PickList {
+    entries: Vec<String>,
+}
+
+impl PickList {
+    /// Reads a list with one entry per line, or separated by commas, semicolons or tabs.
+    /// Empty lines and lines starting with `#` are ignored
+    pub fn load(path: &Path) -> Result<Self, PhogError> {
+        let content = fs::read_to_string(path)
+            .map_err(|err| PhogError::PickList(path.to_path_buf(), err.to_string()))?;
+        Ok(Self::parse(&content))
+    }
+
+    pub fn parse(content: &str) -> Self {
+        let entries = content
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .flat_map(|line| line.split([',', ';', '\t']))
+            .map(|entry| entry.trim().trim_matches('"').trim())
+            .filter(|entry| !entry.is_empty())
+            .map(str::to_string)
+            .collect();
+        Self { entries }
+    }
+
+    /// Pictures matching an entry, in their order
+    pub fn select(&self, pictures: &[PathBuf]) -> Vec<PathBuf> {
+        pictures
+            .iter()
+            .filter(|picture| self.entries.iter().any(|entry| matches(entry, picture)))
+            .cloned()
+            .collect()
+    }
+
+    /// Entries matching none of the pictures
+    pub fn unmatched(&self, pictures: &[PathBuf]) -> Vec<&str> {
+        self.entries
+            .iter()
+            .filter(|entry| !pictures.iter().any(|picture| matches(entry, picture)))
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+/// Wether an entry names a picture: same name with or without extension (`DSC_0042`,
+/// `dsc_0042.raf`, `card/DSC_0042.JPG`) or same frame number (`42` or `0042.jpg` for
+/// `DSC_0042.JPG` and its copy `DSC_0042 (1).JPG`)
+fn matches(entry: &str, picture: &Path) -> bool {
+    let name = entry.rsplit(['/', '\\']).next().unwrap_or(entry);
+    let stem = picture
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_lowercase();
+    let name = name.to_lowercase();
+    let name_stem = name
+        .rsplit_once('.')
+        .map_or(name.as_str(), |(stem, _)| stem);
+    if !name_stem.is_empty() && name_stem.chars().all(|c| c.is_ascii_digit()) {
+        return name_stem
+            .parse::<u64>()
+            .is_ok_and(|frame| frame_number(&stem) == Some(frame));
+    }
+    name == stem || name_stem == stem
+}
+
+/// Number at the end of a file stem, after any copy number (`DSC_0042 (1)` is frame 42)
+fn frame_number(stem: &str) -> Option<u64> {
+    let copy = stem
+        .strip_suffix(')')
+        .and_then(|stem| stem.rsplit_once(" ("));
+    let stem = match copy {
+        Some((stem, copy)) if !copy.is_empty() && copy.chars().all(|c| c.is_ascii_digit()) => stem,
+        _ => stem,
+    };
+    let digits = stem.len() - stem.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    stem[stem.len() - digits..].parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_lists() {
+        let list = PickList::parse("# picks\nDSC_0042, 43;\"44\"\n\n\t45\t\n  # not this\n");
+        assert_eq!(list.entries, ["DSC_0042", "43", "44", "45"]);
+        assert!(PickList::parse("\n# nothing\n , ;").entries.is_empty());
+    }
+
+    #[test]
+    fn match_names_and_frames() {
+        let picture = Path::new("shoot/DSC_0042.JPG");
+        for entry in [
+            "DSC_0042",
+            "dsc_0042.raf",
+            "card/DSC_0042.JPG",
+            "C:\\DSC_0042.jpg",
+        ] {
+            assert!(matches(entry, picture), "{}", entry);
+        }
+        for entry in ["42", "0042", "0042.jpg"] {
+            assert!(matches(entry, picture), "{}", entry);
+            assert!(matches(entry, Path::new("DSC_0042 (1).jpg")), "{}", entry);
+        }
+        for entry in [
+            "DSC_004",
+            "43",
+            "4",
+            "0042.5.jpg",
+            "99999999999999999999999",
+        ] {
+            assert!(!matches(entry, picture), "{}", entry);
+        }
+        assert!(!matches("42", Path::new("DSC_ABCD.jpg")));
+        assert!(!matches("1", Path::new("DSC_0042 (1).jpg")));
+    }
+
+    #[test]
+    fn select_in_order() {
+        let pictures = [
+            PathBuf::from("a_1.jpg"),
+            PathBuf::from("b_2.jpg"),
+            PathBuf::from("c_3.jpg"),
+        ];
+        let list = PickList::parse("3\na_1\nz_9");
+        assert_eq!(
+            list.select(&pictures),
+            [PathBuf::from("a_1.jpg"), PathBuf::from("c_3.jpg")]
+        );
+        assert_eq!(list.unmatched(&pictures), ["z_9"]);
+    }
+}
@@ -0,0 +1,516 @@
+use crate::logic::Sorter;
+use crate::scan::sidecars_of;
+use crate::template::PictureInfo;
+use crate::transfer::{self, staging_path};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Pattern proposed to rename pictures, e.g. `2026-10-18_153012_0042`
+pub const DEFAULT_PATTERN: &str = "{date}_{time}_{frame}";
+
+/// File of a folder journaling the renames applied to its pictures, one plan per line with
+/// the `[old, new]` paths of the files of each picture (relative to the folder when inside
+/// it), so they can be undone
+pub const JOURNAL_NAME: &str = ".phog-renames.jsonl";
+
+/// Plan in the journal: files of each picture renamed, as (old, new) paths
+type Journaled = Vec<Vec<(PathBuf, PathBuf)>>;
+
+/// EXIF placeholders that can be used in a rename pattern
+const EXIF_FIELDS: [&str; 9] = [
+    "year", "month", "day", "date", "hour", "minute", "second", "time", "camera",
+];
+
+/// New name of pictures given by the user, e.g. `{date}_{time}_{frame}`, without extension.
+/// EXIF fields are filled from each picture (the date and time from the file when the EXIF
+/// has none), `{stem}` is the original name, `{frame}` the number at its end and `{n}` (or
+/// `{n:4}` zero padded) counts the pictures
+#[derive(Debug, Clone)]
+pub struct RenamePattern {
+    pattern: String,
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Exif(&'static str),
+    Stem,
+    Frame,
+    /// counter with its width
+    Counter(usize),
+}
+
+impl FromStr for RenamePattern {
+    type Err = String;
+
+    /// Checks the pattern: known placeholders only, and no folder separator
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Err("pattern is empty".into());
+        }
+        if s.contains(['/', '\\']) {
+            return Err("a name can't contain '/' or '\\'".into());
+        }
+        let mut parts = Vec::new();
+        let mut rest = s;
+        while let Some(start) = rest.find(['{', '}']) {
+            if rest[start..].starts_with('}') {
+                return Err("'}' without matching '{'".into());
+            }
+            let Some(len) = rest[start..].find('}') else {
+                return Err("'{' without matching '}'".into());
+            };
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].into()));
+            }
+            let name = &rest[start + 1..start + len];
+            parts.push(match name {
+                "stem" => Part::Stem,
+                "frame" => Part::Frame,
+                "n" => Part::Counter(1),
+                _ => match name.strip_prefix("n:").map(str::parse) {
+                    Some(Ok(width)) => Part::Counter(width),
+                    Some(Err(_)) => return Err(format!("invalid counter width in '{{{}}}'", name)),
+                    None => match EXIF_FIELDS.iter().find(|field| **field == name) {
+                        Some(field) => Part::Exif(field),
+                        None => {
+                            return Err(format!(
+                            "unknown placeholder '{{{}}}' (use {}, {{stem}}, {{frame}} or {{n}})",
+                            name,
+                            EXIF_FIELDS.map(|p| format!("{{{}}}", p)).join(", ")
+                        ))
+                        }
+                    },
+                },
+            });
+            rest = &rest[start + len + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.into()));
+        }
+        Ok(Self {
+            pattern: s.into(),
+            parts,
+        })
+    }
+}
+
+impl fmt::Display for RenamePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.pattern)
+    }
+}
+
+impl RenamePattern {
+    /// New name (without extension) of a picture, counter being its number, or the field
+    /// the picture does not have
+    pub fn name(&self, picture: &Path, counter: usize) -> Result<String, String> {
+        let stem = picture.file_stem().unwrap_or_default().to_string_lossy();
+        let info = self
+            .parts
+            .iter()
+            .any(|part| matches!(part, Part::Exif(_)))
+            .then(|| PictureInfo::read_or_modified(picture));
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => Ok(text.clone()),
+                Part::Exif(field) => info
+                    .as_ref()
+                    .and_then(|info| info.field(field))
+                    .ok_or_else(|| format!("no {{{}}} in its EXIF", field)),
+                Part::Stem => Ok(stem.to_string()),
+                Part::Frame => {
+                    let digits = stem.trim_end_matches(|c: char| c.is_ascii_digit());
+                    Ok(stem[digits.len()..].to_string())
+                }
+                Part::Counter(width) => Ok(format!("{:0width$}", counter, width = width)),
+            })
+            .collect()
+    }
+}
+
+/// A picture to rename with the files that go with it
+#[derive(Debug, Clone)]
+pub struct Rename {
+    pub picture: PathBuf,
+    /// each file (picture, RAW and sidecars) with its new path
+    pub files: Vec<(PathBuf, PathBuf)>,
+}
+
+/// What renaming pictures with a pattern gives
+#[derive(Debug, Clone, Default)]
+pub struct RenamePlan {
+    /// pictures whose name changes, in order
+    pub renames: Vec<Rename>,
+    /// reasons why the pictures can't be renamed, as `file → name: reason` for names used
+    /// twice or taken, `file: reason` for fields a picture does not have
+    pub errors: Vec<String>,
+}
+
+impl RenamePlan {
+    /// Names given by a pattern to pictures (counted from start) and their RAW and sidecars,
+    /// with the fields missing and the names used twice or already taken by other files
+    pub fn new(
+        sorter: &Sorter,
+        pictures: &[PathBuf],
+        pattern: &RenamePattern,
+        start: usize,
+    ) -> Self {
+        let mut plan = Self::default();
+        let mut folder_files: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
+        for (i, picture) in pictures.iter().enumerate() {
+            let folder = picture.parent().unwrap_or(Path::new("")).to_path_buf();
+            let files = folder_files.entry(folder.clone()).or_insert_with(|| {
+                fs::read_dir(&folder)
+                    .map(|entries| entries.flatten().map(|entry| entry.path()).collect())
+                    .unwrap_or_default()
+            });
+            let raw = sorter.raw_in(picture, files);
+            let stem = picture.file_stem().unwrap_or_default().to_string_lossy();
+            let new_stem = match pattern.name(picture, start + i) {
+                Ok(new_stem) => new_stem,
+                Err(err) => {
+                    plan.errors.push(format!("{}: {}", picture.display(), err));
+                    continue;
+                }
+            };
+            if new_stem == stem {
+                continue;
+            }
+            let mut companions: Vec<PathBuf> = raw.iter().cloned().collect();
+            companions.extend(sidecars_of(files, picture, raw.as_deref()));
+            let files = std::iter::once(picture.clone())
+                .chain(companions)
+                .map(|file| {
+                    let name = file.file_name().unwrap_or_default().to_string_lossy();
+                    // companions are named after the picture, e.g. `a.RAF` or `a.RAF.xmp`
+                    let new_name = format!("{}{}", new_stem, &name[stem.len()..]);
+                    let dest = file.with_file_name(new_name);
+                    (file, dest)
+                })
+                .collect();
+            plan.renames.push(Rename {
+                picture: picture.clone(),
+                files,
+            });
+        }
+        plan.errors.extend(plan.collisions());
+        plan
+    }
+
+    /// New names used by two files (in any case), or taken by a file that is not renamed
+    fn collisions(&self) -> Vec<String> {
+        let moved: HashSet<&PathBuf> = self
+            .renames
+            .iter()
+            .flat_map(|rename| rename.files.iter().map(|(file, _)| file))
+            .collect();
+        let mut taken: HashMap<String, &Path> = HashMap::new();
+        let mut collisions = Vec::new();
+        for (file, dest) in self.renames.iter().flat_map(|rename| rename.files.iter()) {
+            let name = dest.file_name().unwrap_or_default().to_string_lossy();
+            let key = dest.to_string_lossy().to_lowercase();
+            if let Some(other) = taken.insert(key, file) {
+                collisions.push(format!(
+                    "{} → {}: also the name of {}",
+                    file.display(),
+                    name,
+                    other.display()
+                ));
+            } else if dest.exists() && !moved.contains(&dest) {
+                collisions.push(format!("{} → {}: already exists", file.display(), name));
+            }
+        }
+        collisions
+    }
+
+    /// Number of files renamed, companions included
+    pub fn file_count(&self) -> usize {
+        self.renames.iter().map(|rename| rename.files.len()).sum()
+    }
+
+    /// Renames all the files or none of them, then journals them in the folder of the sorter
+    /// (only logged in a dry run)
+    pub fn apply(&self, sorter: &Sorter) -> io::Result<()> {
+        self.rename_files(sorter)?;
+        if sorter.get_dry_run().is_none() {
+            let mut plans = read_journal(sorter.get_folder())?;
+            plans.push(
+                self.renames
+                    .iter()
+                    .map(|rename| rename.files.clone())
+                    .collect(),
+            );
+            if let Err(err) = write_journal(sorter.get_folder(), &plans) {
+                println!("Could not record the renames to undo them: {}", err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Gives back their names to the files of the last plan journaled in the folder of the
+    /// sorter, all or none, and forgets it. None if there is nothing to undo
+    pub fn undo_last(sorter: &Sorter) -> io::Result<Option<Self>> {
+        let folder = sorter.get_folder();
+        let mut plans = read_journal(folder)?;
+        let Some(renames) = plans.pop() else {
+            return Ok(None);
+        };
+        let mut undo = Self::default();
+        for files in renames {
+            let files: Vec<(PathBuf, PathBuf)> =
+                files.into_iter().map(|(file, dest)| (dest, file)).collect();
+            if let Some((file, _)) = files.iter().find(|(file, _)| !file.exists()) {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("{} was renamed or removed since", file.display()),
+                ));
+            }
+            if let Some((picture, _)) = files.first() {
+                undo.renames.push(Rename {
+                    picture: picture.clone(),
+                    files,
+                });
+            }
+        }
+        if let Some(collision) = undo.collisions().first() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                collision.clone(),
+            ));
+        }
+        undo.rename_files(sorter)?;
+        if sorter.get_dry_run().is_none() {
+            write_journal(folder, &plans)?;
+        }
+        Ok(Some(undo))
+    }
+
+    /// Each file renamed (picture, RAW and sidecars) with its new path, in order
+    pub fn files(&self) -> impl Iterator<Item = &(PathBuf, PathBuf)> {
+        self.renames.iter().flat_map(|rename| rename.files.iter())
+    }
+
+    /// Renames all the files or none of them (only logged in a dry run). Files go through
+    /// a hidden temporary name first so pictures can swap names
+    fn rename_files(&self, sorter: &Sorter) -> io::Result<()> {
+        let files: Vec<(PathBuf, PathBuf)> = self.files().cloned().collect();
+        if let Some(dry_run) = sorter.get_dry_run() {
+            for (file, dest) in files.iter() {
+                dry_run.record("rename", file, Some(dest));
+            }
+            return Ok(());
+        }
+        // without the extension of the file, so the watcher does not take it for a picture
+        let temporary: Vec<PathBuf> = files
+            .iter()
+            .enumerate()
+            .map(|(i, (file, _))| {
+                let name = file.file_name().unwrap_or_default().to_string_lossy();
+                file.with_file_name(format!(".{}.phog-rename-{}", name, i))
+            })
+            .collect();
+        transfer::move_all_via(&files, &temporary)
+    }
+}
+
+/// Plans journaled in a folder, oldest first, with paths resolved from the folder
+fn read_journal(folder: &Path) -> io::Result<Vec<Journaled>> {
+    let content = match fs::read_to_string(folder.join(JOURNAL_NAME)) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut plan: Journaled = serde_json::from_str(line)?;
+            for (file, dest) in plan.iter_mut().flatten() {
+                *file = folder.join(&file);
+                *dest = folder.join(&dest);
+            }
+            Ok(plan)
+        })
+        .collect()
+}
+
+/// Writes the journal of a folder (removed once empty), next to it then renamed
+fn write_journal(folder: &Path, plans: &[Journaled]) -> io::Result<()> {
+    let path = folder.join(JOURNAL_NAME);
+    if plans.is_empty() {
+        return match fs::remove_file(&path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        };
+    }
+    let relative = |path: &PathBuf| path.strip_prefix(folder).unwrap_or(path).to_path_buf();
+    let staged = staging_path(&path);
+    let written = fs::File::create(&staged).and_then(|mut journal| {
+        for plan in plans {
+            let mut plan = plan.clone();
+            for (file, dest) in plan.iter_mut().flatten() {
+                *file = relative(file);
+                *dest = relative(dest);
+            }
+            writeln!(journal, "{}", serde_json::to_string(&plan)?)?;
+        }
+        journal.sync_all()
+    });
+    if let Err(err) = written.and_then(|_| fs::rename(&staged, &path)) {
+        let _ = fs::remove_file(&staged);
+        return Err(err);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::tests::sorter;
+    use crate::template::tests::write_jpeg;
+
+    fn pattern(s: &str) -> RenamePattern {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_patterns() {
+        assert_eq!(
+            pattern("x_{stem}-{frame}{n:4}{date}").parts,
+            [
+                Part::Text("x_".into()),
+                Part::Stem,
+                Part::Text("-".into()),
+                Part::Frame,
+                Part::Counter(4),
+                Part::Exif("date"),
+            ]
+        );
+        assert_eq!(pattern("{n}").parts, [Part::Counter(1)]);
+        for invalid in [
+            "", " ", "{n", "n}", "{{n}", "a/{n}", "a\\b", "{n:x}", "{size}",
+        ] {
+            assert!(invalid.parse::<RenamePattern>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn names_from_pattern() {
+        let dir = tempfile::tempdir().unwrap();
+        let picture = dir.path().join("DSC_0042.JPG");
+        write_jpeg(&picture, "2026:10:18 15:30:12", "X-T5");
+        let name = |s, picture: &Path, counter| pattern(s).name(picture, counter).unwrap();
+        assert_eq!(name("{frame}", &picture, 1), "0042");
+        assert_eq!(name("{frame}", Path::new("IMG.jpg"), 1), "");
+        assert_eq!(name("{stem}_{n:3}", &picture, 7), "DSC_0042_007");
+        assert_eq!(name("{n:2}", &picture, 123), "123");
+        assert_eq!(
+            name("{date}_{time}_{frame}", &picture, 1),
+            "2026-10-18_153012_0042"
+        );
+        assert_eq!(name("{camera}_{hour}h", &picture, 1), "X-T5_15h");
+        // the date of the file when the EXIF has none, no placeholder value made up
+        let plain = dir.path().join("plain.jpg");
+        fs::write(&plain, "").unwrap();
+        assert_eq!(
+            name("{date}", &plain, 1),
+            chrono::Local::now().format("%Y-%m-%d").to_string()
+        );
+        assert!(pattern("{camera}_{n}")
+            .name(&plain, 2)
+            .unwrap_err()
+            .contains("{camera}"));
+    }
+
+    #[test]
+    fn plan_companions_and_collisions() {
+        let dir = tempfile::tempdir().unwrap();
+        for file in [
+            "a_1.jpg",
+            "a_1.RAF",
+            "a_1.RAF.xmp",
+            "b_2.jpg",
+            "taken_3.jpg",
+            "c_3.jpg",
+        ] {
+            fs::write(dir.path().join(file), file).unwrap();
+        }
+        let pictures = ["a_1.jpg", "b_2.jpg", "c_3.jpg"].map(|file| dir.path().join(file));
+        let plan = RenamePlan::new(&sorter(dir.path()), &pictures, &pattern("new_{frame}"), 1);
+        let names: Vec<String> = plan
+            .files()
+            .map(|(_, dest)| dest.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "new_1.jpg",
+                "new_1.RAF",
+                "new_1.RAF.xmp",
+                "new_2.jpg",
+                "new_3.jpg"
+            ]
+        );
+        assert!(plan.errors.is_empty());
+        // names taken by a file that stays, or used twice
+        let plan = RenamePlan::new(&sorter(dir.path()), &pictures, &pattern("taken_{n}"), 2);
+        assert_eq!(plan.errors.len(), 1);
+        let plan = RenamePlan::new(&sorter(dir.path()), &pictures, &pattern("same"), 1);
+        assert_eq!(plan.errors.len(), 2);
+        // fields the pictures don't have
+        let plan = RenamePlan::new(&sorter(dir.path()), &pictures, &pattern("{camera}"), 1);
+        assert_eq!(plan.errors.len(), 3);
+        assert!(plan.renames.is_empty());
+    }
+
+    #[test]
+    fn apply_and_undo() {
+        let dir = tempfile::tempdir().unwrap();
+        for file in ["a.jpg", "a.RAF", "b.jpg"] {
+            fs::write(dir.path().join(file), file).unwrap();
+        }
+        let sorter = sorter(dir.path());
+        // pictures swap names
+        let pictures = [dir.path().join("b.jpg"), dir.path().join("a.jpg")];
+        let plan = RenamePlan::new(&sorter, &pictures, &pattern("{n}"), 1);
+        plan.apply(&sorter).unwrap();
+        let read = |file: &str| fs::read_to_string(dir.path().join(file)).unwrap();
+        assert_eq!(
+            (read("1.jpg"), read("2.jpg"), read("2.RAF")),
+            ("b.jpg".into(), "a.jpg".into(), "a.RAF".into())
+        );
+        let plan = RenamePlan::new(&sorter, &[dir.path().join("1.jpg")], &pattern("b"), 1);
+        plan.apply(&sorter).unwrap();
+        assert_eq!(read("b.jpg"), "b.jpg");
+        // undone in reverse order
+        assert_eq!(
+            RenamePlan::undo_last(&sorter)
+                .unwrap()
+                .unwrap()
+                .renames
+                .len(),
+            1
+        );
+        assert_eq!(read("1.jpg"), "b.jpg");
+        RenamePlan::undo_last(&sorter).unwrap();
+        assert_eq!(
+            (read("a.jpg"), read("a.RAF"), read("b.jpg")),
+            ("a.jpg".into(), "a.RAF".into(), "b.jpg".into())
+        );
+        assert!(RenamePlan::undo_last(&sorter).unwrap().is_none());
+        let mut names: Vec<String> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        // no temporary file nor journal left
+        assert_eq!(names, ["a.RAF", "a.jpg", "b.jpg"]);
+    }
+}
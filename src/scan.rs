@@ -0,0 +1,237 @@
+use crate::error::PhogError;
+use crate::logic::{list_pictures, SortOrder, Sorter};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Extensions of the files editors keep next to a picture or its RAW (`a.xmp` or `a.RAF.xmp`)
+pub const SIDECAR_EXTENSIONS: [&str; 3] = ["xmp", "pp3", "dop"];
+
+/// A picture with the files that go with it, names relative to the folder
+#[derive(Debug, Clone, Serialize)]
+pub struct Shot {
+    pub picture: String,
+    pub raw: Option<String>,
+    pub sidecars: Vec<String>,
+    /// stars given in an editor (`xmp:Rating` of the XMP sidecars)
+    pub rating: Option<i32>,
+    /// color label given in an editor (`xmp:Label` of the XMP sidecars)
+    pub label: Option<String>,
+}
+
+impl Shot {
+    /// The same shot, its files named by their path in a folder
+    pub fn in_folder(&self, folder: &Path) -> Self {
+        let path = |name: &String| folder.join(name).to_string_lossy().into_owned();
+        Self {
+            picture: path(&self.picture),
+            raw: self.raw.as_ref().map(path),
+            sidecars: self.sidecars.iter().map(path).collect(),
+            rating: self.rating,
+            label: self.label.clone(),
+        }
+    }
+}
+
+/// What the app sees in a folder: pictures in browsing order with their RAW and sidecars,
+/// and the RAW files without a picture
+#[derive(Debug, Clone, Serialize)]
+pub struct Scan {
+    pub folder: PathBuf,
+    pub shots: Vec<Shot>,
+    pub orphan_raws: Vec<String>,
+}
+
+impl Scan {
+    pub fn new(sorter: &Sorter, sort: SortOrder) -> Result<Self, PhogError> {
+        let folder = sorter.get_folder();
+        let pictures = list_pictures(folder, sort)?;
+        let files: Vec<PathBuf> = fs::read_dir(folder)
+            .map_err(|err| PhogError::FolderScan(folder.to_path_buf(), err.to_string()))?
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .collect();
+        let mut shots = Vec::new();
+        for picture in pictures.iter() {
+            let raw = sorter.raw_in(picture, &files);
+            let sidecars: Vec<String> = sidecars_of(&files, picture, raw.as_deref())
+                .iter()
+                .map(|file| name(file))
+                .collect();
+            let xmps: Vec<String> = sidecars
+                .iter()
+                .filter(|sidecar| sidecar.to_lowercase().ends_with(".xmp"))
+                .filter_map(|sidecar| fs::read_to_string(folder.join(sidecar)).ok())
+                .collect();
+            let property = |name: &str| xmps.iter().find_map(|xmp| xmp_property(xmp, name));
+            shots.push(Shot {
+                picture: name(picture),
+                raw: raw.as_deref().map(name),
+                rating: property("xmp:Rating").and_then(|rating| rating.parse().ok()),
+                label: property("xmp:Label"),
+                sidecars,
+            });
+        }
+        Ok(Self {
+            folder: folder.to_path_buf(),
+            shots,
+            orphan_raws: sorter.orphan_raws()?.iter().map(|raw| name(raw)).collect(),
+        })
+    }
+
+    /// Pictures without a RAW
+    pub fn jpeg_only(&self) -> impl Iterator<Item = &Shot> {
+        self.shots.iter().filter(|shot| shot.raw.is_none())
+    }
+}
+
+/// Sidecars of a picture and its RAW among files, sorted (`a.xmp`, `a.JPG.xmp`, `a.RAF.xmp`)
+pub fn sidecars_of(files: &[PathBuf], picture: &Path, raw: Option<&Path>) -> Vec<PathBuf> {
+    let mut owners = vec![picture.with_extension(""), picture.to_path_buf()];
+    if let Some(raw) = raw {
+        owners.push(raw.to_path_buf());
+    }
+    let mut sidecars: Vec<PathBuf> = files
+        .iter()
+        .filter(|file| is_sidecar(file) && owners.contains(&file.with_extension("")))
+        .cloned()
+        .collect();
+    sidecars.sort();
+    sidecars
+}
+
+/// Value of a simple XMP property, written as an attribute (`xmp:Rating="3"`)
+/// or an element (`<xmp:Rating>3</xmp:Rating>`)
+fn xmp_property(xmp: &str, name: &str) -> Option<String> {
+    let value = if let Some((_, rest)) = xmp.split_once(&format!("{}=\"", name)) {
+        rest.split_once('"')?.0
+    } else {
+        let (_, rest) = xmp.split_once(&format!("<{}>", name))?;
+        rest.split_once(&format!("</{}>", name))?.0
+    };
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+fn is_sidecar(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| {
+        SIDECAR_EXTENSIONS
+            .iter()
+            .any(|sidecar| ext.eq_ignore_ascii_case(sidecar))
+    })
+}
+
+fn name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::tests::sorter;
+
+    #[test]
+    fn sidecars_of_a_picture_and_its_raw() {
+        let files: Vec<PathBuf> = [
+            "a.jpg",
+            "a.RAF",
+            "a.xmp",
+            "a.jpg.XMP",
+            "a.RAF.pp3",
+            "a.RAF.dop",
+            "ab.xmp",
+            "b.xmp",
+            "a.txt",
+        ]
+        .iter()
+        .map(|name| PathBuf::from("photos").join(name))
+        .collect();
+        let picture = Path::new("photos/a.jpg");
+        let raw = Path::new("photos/a.RAF");
+        let names = |sidecars: Vec<PathBuf>| -> Vec<String> {
+            sidecars
+                .iter()
+                .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+                .collect()
+        };
+        assert_eq!(
+            names(sidecars_of(&files, picture, Some(raw))),
+            ["a.RAF.dop", "a.RAF.pp3", "a.jpg.XMP", "a.xmp"]
+        );
+        assert_eq!(
+            names(sidecars_of(&files, picture, None)),
+            ["a.jpg.XMP", "a.xmp"]
+        );
+    }
+
+    #[test]
+    fn shots_and_orphan_raws() {
+        let dir = tempfile::tempdir().unwrap();
+        for file in [
+            "a.jpg",
+            "a.RAF",
+            "a.xmp",
+            "b.png",
+            "c.RAF",
+            "c.RAF.xmp",
+            "notes.txt",
+        ] {
+            fs::write(dir.path().join(file), "").unwrap();
+        }
+        fs::write(
+            dir.path().join("a.xmp"),
+            r#"<rdf:Description xmp:Rating="4"><xmp:Label>Red</xmp:Label></rdf:Description>"#,
+        )
+        .unwrap();
+        // a folder named like a RAW is not one
+        fs::create_dir(dir.path().join("d.RAF")).unwrap();
+        let scan = Scan::new(&sorter(dir.path()), SortOrder::Name).unwrap();
+        let shots: Vec<(&str, Option<&str>, &[String])> = scan
+            .shots
+            .iter()
+            .map(|shot| {
+                (
+                    shot.picture.as_str(),
+                    shot.raw.as_deref(),
+                    &shot.sidecars[..],
+                )
+            })
+            .collect();
+        assert_eq!(
+            shots,
+            [
+                ("a.jpg", Some("a.RAF"), &["a.xmp".to_string()][..]),
+                ("b.png", None, &[][..])
+            ]
+        );
+        assert_eq!(scan.shots[0].rating, Some(4));
+        assert_eq!(scan.shots[0].label.as_deref(), Some("Red"));
+        assert_eq!(scan.shots[1].rating, None);
+        assert_eq!(scan.jpeg_only().count(), 1);
+        assert_eq!(scan.orphan_raws, ["c.RAF"]);
+    }
+
+    #[test]
+    fn xmp_properties() {
+        let xmp = r#"<rdf:Description xmp:Rating="3" xmp:Label="">
+            <xmp:Label> Green </xmp:Label>
+            <xmp:CreatorTool>darktable</xmp:CreatorTool>"#;
+        assert_eq!(xmp_property(xmp, "xmp:Rating").as_deref(), Some("3"));
+        // an empty attribute is no value, even if an element follows
+        assert_eq!(xmp_property(xmp, "xmp:Label"), None);
+        assert_eq!(
+            xmp_property(xmp, "xmp:CreatorTool").as_deref(),
+            Some("darktable")
+        );
+        assert_eq!(
+            xmp_property("<xmp:Label> Green </xmp:Label>", "xmp:Label").as_deref(),
+            Some("Green")
+        );
+        assert_eq!(xmp_property(xmp, "xmp:Rat"), None);
+        assert_eq!(xmp_property("<xmp:Rating>3", "xmp:Rating"), None);
+    }
+}
@@ -0,0 +1,152 @@
+use crate::error::PhogError;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// File keeping the decisions taken in a folder, so they survive a restart
+pub const SESSION_NAME: &str = ".phog-session.json";
+
+/// A picture with the buckets it was decided to send it to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PictureDecisions {
+    /// relative to the folder of the session unless absolute
+    pub file: PathBuf,
+    /// names of the buckets (edit and bin included), in the order they were decided
+    pub buckets: Vec<String>,
+}
+
+/// Decisions taken on the pictures of a folder, in the order they were taken
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Session {
+    pub pictures: Vec<PictureDecisions>,
+}
+
+impl Session {
+    /// Reads a session file, an empty session if there is none
+    pub fn load(path: &Path) -> Result<Self, PhogError> {
+        let error = |err: String| PhogError::Session(path.to_path_buf(), err);
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(error(err.to_string())),
+        };
+        serde_json::from_str(&content).map_err(|err| error(err.to_string()))
+    }
+
+    /// Writes the session file, removing it once nothing is pending
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if self.pictures.is_empty() {
+            return match fs::remove_file(path) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+                _ => Ok(()),
+            };
+        }
+        let content = serde_json::to_string_pretty(self)?;
+        fs::write(path, content)
+    }
+
+    /// Buckets a picture was decided to go to
+    pub fn buckets(&self, file: &Path) -> &[String] {
+        self.pictures
+            .iter()
+            .find(|picture| picture.file == file)
+            .map_or(&[], |picture| &picture.buckets)
+    }
+
+    /// Adds the decision to send a picture to a bucket if it was not taken, else removes it.
+    /// Returns wether it is now taken
+    pub fn toggle(&mut self, file: &Path, bucket: &str) -> bool {
+        if self.buckets(file).iter().any(|name| name == bucket) {
+            self.remove(file, |name| name == bucket);
+            return false;
+        }
+        self.take(file, bucket)
+    }
+
+    /// Adds the decision to send a picture to a bucket if it was not taken.
+    /// Returns wether it was added
+    pub fn take(&mut self, file: &Path, bucket: &str) -> bool {
+        if self.buckets(file).iter().any(|name| name == bucket) {
+            return false;
+        }
+        match self
+            .pictures
+            .iter_mut()
+            .find(|picture| picture.file == file)
+        {
+            Some(picture) => picture.buckets.push(bucket.into()),
+            None => self.pictures.push(PictureDecisions {
+                file: file.to_path_buf(),
+                buckets: vec![bucket.into()],
+            }),
+        }
+        true
+    }
+
+    /// Removes the decisions on a picture whose bucket matches the predicate
+    pub fn remove(&mut self, file: &Path, predicate: impl Fn(&str) -> bool) {
+        for picture in self.pictures.iter_mut() {
+            if picture.file == file {
+                picture.buckets.retain(|name| !predicate(name));
+            }
+        }
+        self.pictures.retain(|picture| !picture.buckets.is_empty());
+    }
+
+    /// Follows a picture that was renamed
+    pub fn rename(&mut self, file: &Path, new_file: &Path) {
+        for picture in self.pictures.iter_mut() {
+            if picture.file == file {
+                picture.file = new_file.to_path_buf();
+            }
+        }
+    }
+
+    /// Number of decisions not applied yet
+    pub fn pending(&self) -> usize {
+        self.pictures
+            .iter()
+            .map(|picture| picture.buckets.len())
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toggle_decisions() {
+        let mut session = Session::default();
+        let (a, b) = (Path::new("a.jpg"), Path::new("b.jpg"));
+        assert!(session.take(a, "edit"));
+        assert!(!session.take(a, "edit"));
+        assert!(session.toggle(a, "print"));
+        assert!(session.toggle(b, "bin"));
+        assert_eq!(session.buckets(a), ["edit", "print"]);
+        assert_eq!(session.pending(), 3);
+        assert!(!session.toggle(a, "edit"));
+        assert!(!session.toggle(b, "bin"));
+        assert_eq!(session.buckets(a), ["print"]);
+        assert!(session.buckets(b).is_empty());
+        assert_eq!(session.pictures.len(), 1);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SESSION_NAME);
+        assert!(Session::load(&path).unwrap().pictures.is_empty());
+        let mut session = Session::default();
+        session.toggle(Path::new("a.jpg"), "edit");
+        session.save(&path).unwrap();
+        let loaded = Session::load(&path).unwrap();
+        assert_eq!(loaded.buckets(Path::new("a.jpg")), ["edit"]);
+        fs::write(&path, "{").unwrap();
+        assert!(Session::load(&path).is_err());
+        // nothing pending removes the file
+        Session::default().save(&path).unwrap();
+        assert!(!path.exists());
+    }
+}
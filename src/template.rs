@@ -0,0 +1,320 @@
+use exif::{In, Tag, Value};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Placeholders that can be used in a folder template
+const PLACEHOLDERS: [&str; 5] = ["year", "month", "day", "date", "camera"];
+
+/// Value used when a picture has no EXIF data for a placeholder
+const UNKNOWN: &str = "unknown";
+
+/// Destination folder given by the user, e.g. `~/Pictures/{year}/{date}_{camera}`.
+/// Placeholders are filled from the EXIF of each picture
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub struct FolderTemplate {
+    template: String,
+}
+
+impl FromStr for FolderTemplate {
+    type Err = String;
+
+    /// Checks the template: known placeholders only, and a home folder to expand `~` with
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Err("folder is empty".into());
+        }
+        if starts_with_home(s) && home_dir().is_none() {
+            return Err("home folder is unknown, can't expand '~'".into());
+        }
+        let mut rest = s;
+        while let Some(start) = rest.find(['{', '}']) {
+            if rest[start..].starts_with('}') {
+                return Err("'}' without matching '{'".into());
+            }
+            let Some(len) = rest[start..].find('}') else {
+                return Err("'{' without matching '}'".into());
+            };
+            let name = &rest[start + 1..start + len];
+            if !PLACEHOLDERS.contains(&name) {
+                return Err(format!(
+                    "unknown placeholder '{{{}}}' (use {})",
+                    name,
+                    PLACEHOLDERS.map(|p| format!("{{{}}}", p)).join(", ")
+                ));
+            }
+            rest = &rest[start + len + 1..];
+        }
+        Ok(Self { template: s.into() })
+    }
+}
+
+impl TryFrom<String> for FolderTemplate {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for FolderTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.template)
+    }
+}
+
+impl FolderTemplate {
+    /// Folder for picture, relative paths being inside base
+    pub fn resolve(&self, base: &Path, picture: &Path) -> PathBuf {
+        self.resolve_with(base, picture, PictureInfo::read)
+    }
+
+    /// Like resolve, the modification date of the file standing in for a missing EXIF date
+    pub fn resolve_dated(&self, base: &Path, picture: &Path) -> PathBuf {
+        self.resolve_with(base, picture, PictureInfo::read_or_modified)
+    }
+
+    fn resolve_with(&self, base: &Path, picture: &Path, read: fn(&Path) -> PictureInfo) -> PathBuf {
+        let mut resolved = self.template.clone();
+        if resolved.contains('{') {
+            let info = read(picture);
+            for name in PLACEHOLDERS {
+                let placeholder = format!("{{{}}}", name);
+                if resolved.contains(&placeholder) {
+                    resolved = resolved.replace(&placeholder, &info.get(name));
+                }
+            }
+        }
+        let path = match home_dir() {
+            Some(home) if starts_with_home(&resolved) => {
+                home.join(resolved[1..].trim_start_matches(['/', '\\']))
+            }
+            _ => PathBuf::from(resolved),
+        };
+        base.join(path)
+    }
+}
+
+/// EXIF data used to fill the placeholders
+pub(crate) struct PictureInfo {
+    /// (year, month, day) the picture was taken
+    date: Option<(String, String, String)>,
+    /// (hour, minute, second) the picture was taken
+    time: Option<(String, String, String)>,
+    camera: Option<String>,
+}
+
+impl PictureInfo {
+    pub(crate) fn read(picture: &Path) -> Self {
+        let mut info = Self {
+            date: None,
+            time: None,
+            camera: None,
+        };
+        let Ok(file) = fs::File::open(picture) else {
+            return info;
+        };
+        let mut buffer_reader = std::io::BufReader::new(&file);
+        let Ok(exif) = exif::Reader::new().read_from_container(&mut buffer_reader) else {
+            return info;
+        };
+        let text = |tag| match exif.get_field(tag, In::PRIMARY).map(|field| &field.value) {
+            Some(Value::Ascii(values)) => values
+                .first()
+                .map(|value| String::from_utf8_lossy(value).trim().to_string())
+                .filter(|value| !value.is_empty()),
+            _ => None,
+        };
+        // "YYYY:MM:DD HH:MM:SS"
+        let date_time = text(Tag::DateTimeOriginal).or_else(|| text(Tag::DateTime));
+        let parts: Vec<String> = date_time
+            .iter()
+            .flat_map(|date_time| date_time.split([':', ' ']))
+            .map(str::to_string)
+            .collect();
+        if let [year, month, day, rest @ ..] = &parts[..] {
+            info.date = Some((year.clone(), month.clone(), day.clone()));
+            if let [hour, minute, second, ..] = rest {
+                info.time = Some((hour.clone(), minute.clone(), second.clone()));
+            }
+        }
+        info.camera = text(Tag::Model);
+        info
+    }
+
+    /// Like read, the local modification time of the file standing in for a missing EXIF date
+    pub(crate) fn read_or_modified(picture: &Path) -> Self {
+        let mut info = Self::read(picture);
+        if info.date.is_some() {
+            return info;
+        }
+        let Ok(modified) = fs::metadata(picture).and_then(|metadata| metadata.modified()) else {
+            return info;
+        };
+        let modified = chrono::DateTime::<chrono::Local>::from(modified);
+        let field = |format: &str| modified.format(format).to_string();
+        info.date = Some((field("%Y"), field("%m"), field("%d")));
+        info.time = Some((field("%H"), field("%M"), field("%S")));
+        info
+    }
+
+    /// Value of a placeholder, made safe to be used in a folder name
+    fn get(&self, name: &str) -> String {
+        self.field(name).unwrap_or(UNKNOWN.into())
+    }
+
+    /// Value of a placeholder made safe to be used in a folder or file name, None when the
+    /// picture does not have it
+    pub(crate) fn field(&self, name: &str) -> Option<String> {
+        let value = match (name, &self.date, &self.time) {
+            ("year", Some((year, _, _)), _) => year.clone(),
+            ("month", Some((_, month, _)), _) => month.clone(),
+            ("day", Some((_, _, day)), _) => day.clone(),
+            ("date", Some((year, month, day)), _) => format!("{}-{}-{}", year, month, day),
+            ("hour", _, Some((hour, _, _))) => hour.clone(),
+            ("minute", _, Some((_, minute, _))) => minute.clone(),
+            ("second", _, Some((_, _, second))) => second.clone(),
+            ("time", _, Some((hour, minute, second))) => format!("{}{}{}", hour, minute, second),
+            ("camera", ..) => self.camera.clone()?,
+            _ => return None,
+        };
+        Some(
+            value
+                .chars()
+                .map(|c| match c {
+                    '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+                    c => c,
+                })
+                .collect(),
+        )
+    }
+}
+
+fn starts_with_home(path: &str) -> bool {
+    path == "~" || path.starts_with("~/") || path.starts_with("~\\")
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+
+    /// Writes a minimal JPEG whose EXIF has these date ("YYYY:MM:DD HH:MM:SS") and camera
+    pub(crate) fn write_jpeg(path: &Path, date_time: &str, camera: &str) {
+        let fields =
+            [(Tag::DateTimeOriginal, date_time), (Tag::Model, camera)].map(|(tag, text)| {
+                exif::Field {
+                    tag,
+                    ifd_num: In::PRIMARY,
+                    value: Value::Ascii(vec![text.as_bytes().to_vec()]),
+                }
+            });
+        let mut writer = exif::experimental::Writer::new();
+        for field in fields.iter() {
+            writer.push_field(field);
+        }
+        let mut tiff = Cursor::new(Vec::new());
+        writer.write(&mut tiff, false).unwrap();
+        let tiff = tiff.into_inner();
+        let mut file = fs::File::create(path).unwrap();
+        file.write_all(&[0xFF, 0xD8, 0xFF, 0xE1]).unwrap();
+        file.write_all(&((tiff.len() + 8) as u16).to_be_bytes())
+            .unwrap();
+        file.write_all(b"Exif\0\0").unwrap();
+        file.write_all(&tiff).unwrap();
+        file.write_all(&[0xFF, 0xD9]).unwrap();
+    }
+
+    #[test]
+    fn parse_templates() {
+        for valid in [
+            "edit",
+            "/mnt/print/{year}",
+            "{date}_{camera}",
+            "{year}/{month}/{day}",
+        ] {
+            let template: FolderTemplate = valid.parse().unwrap();
+            assert_eq!(template.to_string(), valid);
+        }
+        assert!(" ".parse::<FolderTemplate>().is_err());
+        assert!("{time}"
+            .parse::<FolderTemplate>()
+            .unwrap_err()
+            .contains("unknown"));
+        assert!("{year"
+            .parse::<FolderTemplate>()
+            .unwrap_err()
+            .contains("'{'"));
+        assert!("year}"
+            .parse::<FolderTemplate>()
+            .unwrap_err()
+            .contains("'}'"));
+    }
+
+    #[test]
+    fn resolve_from_exif() {
+        let dir = tempfile::tempdir().unwrap();
+        let picture = dir.path().join("a.jpg");
+        write_jpeg(&picture, "2026:10:18 15:30:12", "X-T5/1");
+        let template: FolderTemplate = "{year}/{date}_{camera}".parse().unwrap();
+        let base = Path::new("/photos");
+        assert_eq!(
+            template.resolve(base, &picture),
+            Path::new("/photos/2026/2026-10-18_X-T5_1")
+        );
+        // relative to the base unless absolute
+        let absolute: FolderTemplate = "/mnt/{month}-{day}".parse().unwrap();
+        assert_eq!(absolute.resolve(base, &picture), Path::new("/mnt/10-18"));
+    }
+
+    #[test]
+    fn resolve_without_exif() {
+        let template: FolderTemplate = "{year}/{camera}".parse().unwrap();
+        assert_eq!(
+            template.resolve(Path::new("base"), Path::new("missing.jpg")),
+            Path::new("base/unknown/unknown")
+        );
+    }
+
+    #[test]
+    fn resolve_from_modification_date() {
+        let dir = tempfile::tempdir().unwrap();
+        let (exif, plain) = (dir.path().join("exif.jpg"), dir.path().join("plain.jpg"));
+        write_jpeg(&exif, "2024:03:05 14:07:09", "X-T5");
+        fs::write(&plain, "").unwrap();
+        let template: FolderTemplate = "{year}/{date}".parse().unwrap();
+        assert_eq!(
+            template.resolve_dated(Path::new("base"), &exif),
+            Path::new("base/2024/2024-03-05")
+        );
+        let modified = chrono::Local::now();
+        assert_eq!(
+            template.resolve_dated(Path::new("base"), &plain),
+            Path::new("base").join(modified.format("%Y/%Y-%m-%d").to_string())
+        );
+        assert_eq!(
+            template.resolve(Path::new("base"), &plain),
+            Path::new("base/unknown/unknown")
+        );
+    }
+
+    #[test]
+    fn resolve_home() {
+        let Some(home) = home_dir() else {
+            return;
+        };
+        let template: FolderTemplate = "~/Pictures".parse().unwrap();
+        assert_eq!(
+            template.resolve(Path::new("base"), Path::new("a.jpg")),
+            home.join("Pictures")
+        );
+    }
+}
@@ -0,0 +1,237 @@
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+
+/// How a file is put in its destination
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkMode {
+    /// Full copy of the file
+    Copy,
+    /// Hard link, no extra space used (same filesystem only)
+    Hardlink,
+    /// Symbolic link to the original file
+    Symlink,
+}
+
+/// Puts a file in its destination using mode, falling back to a copy if a link can't be made
+/// (e.g. hard link across filesystems). Returns the mode that was actually used
+pub fn link_or_copy(mode: LinkMode, from: &Path, to: &Path) -> io::Result<LinkMode> {
+    let linked = match mode {
+        LinkMode::Copy => return fs::copy(from, to).map(|_| LinkMode::Copy),
+        LinkMode::Hardlink => fs::hard_link(from, to),
+        LinkMode::Symlink => symlink(&fs::canonicalize(from)?, to),
+    };
+    match linked {
+        Ok(()) => Ok(mode),
+        // a file in the way is not replaced by the copy
+        Err(err) if err.kind() != io::ErrorKind::AlreadyExists && from.exists() => {
+            fs::copy(from, to).map(|_| LinkMode::Copy)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Moves a file. If it can't simply be renamed because the destination is on another
+/// filesystem, it is copied, the copy is checked and then the original is removed
+pub fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(err) if err.kind() == io::ErrorKind::CrossesDevices => (),
+        renamed => return renamed,
+    }
+    let checked = fs::copy(from, to).and_then(|_| match same_content(from, to) {
+        Ok(true) => fs::remove_file(from),
+        Ok(false) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "copy differs from the original",
+        )),
+        Err(err) => Err(err),
+    });
+    if checked.is_err() {
+        // original left untouched, a partial copy (e.g. full disk) removed
+        let _ = fs::remove_file(to);
+    }
+    checked
+}
+
+/// Moves all the files or none of them: if one move fails, the files already moved are put back
+pub fn move_all(moves: &[(PathBuf, PathBuf)]) -> io::Result<()> {
+    for (i, (from, to)) in moves.iter().enumerate() {
+        if let Err(err) = move_file(from, to) {
+            for (from, to) in moves[..i].iter().rev() {
+                let _ = move_file(to, from);
+            }
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+/// Moves all the files or none of them through temporary paths: every file is moved to its
+/// temporary path first, then to its destination. Files can so swap names, and a file already
+/// at a destination is only replaced once all the moves to the temporary paths succeeded
+pub fn move_all_via(moves: &[(PathBuf, PathBuf)], temporary: &[PathBuf]) -> io::Result<()> {
+    let first: Vec<(PathBuf, PathBuf)> = moves
+        .iter()
+        .zip(temporary)
+        .map(|((from, _), tmp)| (from.clone(), tmp.clone()))
+        .collect();
+    move_all(&first)?;
+    let last: Vec<(PathBuf, PathBuf)> = moves
+        .iter()
+        .zip(temporary)
+        .map(|((_, to), tmp)| (tmp.clone(), to.clone()))
+        .collect();
+    if let Err(err) = move_all(&last) {
+        let back: Vec<(PathBuf, PathBuf)> =
+            first.into_iter().map(|(from, tmp)| (tmp, from)).collect();
+        let _ = move_all(&back);
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// Temporary path next to a destination, hidden and without the extension of the file so it
+/// is not taken for a picture
+pub fn staging_path(dest: &Path) -> PathBuf {
+    let name = dest.file_name().unwrap_or_default().to_string_lossy();
+    dest.with_file_name(format!(".{}.phog-tmp", name))
+}
+
+/// Compares two files byte by byte
+pub fn same_content(a: &Path, b: &Path) -> io::Result<bool> {
+    if fs::metadata(a)?.len() != fs::metadata(b)?.len() {
+        return Ok(false);
+    }
+    let mut reader_a = io::BufReader::new(fs::File::open(a)?);
+    let mut reader_b = io::BufReader::new(fs::File::open(b)?);
+    loop {
+        let chunk_a = reader_a.fill_buf()?;
+        let chunk_b = reader_b.fill_buf()?;
+        let len = chunk_a.len().min(chunk_b.len());
+        if len == 0 {
+            return Ok(chunk_a.len() == chunk_b.len());
+        }
+        if chunk_a[..len] != chunk_b[..len] {
+            return Ok(false);
+        }
+        reader_a.consume(len);
+        reader_b.consume(len);
+    }
+}
+
+#[cfg(unix)]
+pub fn symlink(original: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(original, link)
+}
+
+#[cfg(windows)]
+pub fn symlink(original: &Path, link: &Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_file(original, link)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_or_copies() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.jpg");
+        fs::write(&file, "a").unwrap();
+        let to = |name: &str| dir.path().join(name);
+        assert_eq!(
+            link_or_copy(LinkMode::Hardlink, &file, &to("hard.jpg")).unwrap(),
+            LinkMode::Hardlink
+        );
+        assert_eq!(
+            link_or_copy(LinkMode::Symlink, &file, &to("sym.jpg")).unwrap(),
+            LinkMode::Symlink
+        );
+        assert!(to("sym.jpg").is_symlink());
+        // a file in the way is kept
+        assert!(link_or_copy(LinkMode::Hardlink, &file, &to("sym.jpg")).is_err());
+        assert!(to("sym.jpg").is_symlink());
+        assert!(link_or_copy(LinkMode::Copy, &to("b.jpg"), &to("c.jpg")).is_err());
+        fs::write(&file, "b").unwrap();
+        assert_eq!(fs::read(to("hard.jpg")).unwrap(), b"b");
+    }
+
+    #[test]
+    fn move_all_or_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a.jpg"), dir.path().join("a.RAF"));
+        fs::write(&a, "jpg").unwrap();
+        fs::write(&b, "raf").unwrap();
+        let dest = dir.path().join("bin");
+        fs::create_dir(&dest).unwrap();
+        let moves = [
+            (a.clone(), dest.join("a.jpg")),
+            (b.clone(), dest.join("a.RAF")),
+            (dir.path().join("missing"), dest.join("missing")),
+        ];
+        assert!(move_all(&moves).is_err());
+        // first two moves put back
+        assert_eq!(fs::read_to_string(&a).unwrap(), "jpg");
+        assert_eq!(fs::read_to_string(&b).unwrap(), "raf");
+        assert!(!dest.join("a.jpg").exists());
+
+        assert!(move_all(&moves[..2]).is_ok());
+        assert!(!a.exists());
+        assert_eq!(fs::read_to_string(dest.join("a.RAF")).unwrap(), "raf");
+    }
+
+    #[test]
+    fn move_via_temporary_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a.jpg"), dir.path().join("b.jpg"));
+        fs::write(&a, "a").unwrap();
+        fs::write(&b, "b").unwrap();
+        // swapping names
+        let moves = [(a.clone(), b.clone()), (b.clone(), a.clone())];
+        let temporary = [staging_path(&b), staging_path(&a)];
+        move_all_via(&moves, &temporary).unwrap();
+        assert_eq!(fs::read_to_string(&a).unwrap(), "b");
+        assert_eq!(fs::read_to_string(&b).unwrap(), "a");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+
+        // nothing replaced when a file can't be moved
+        let missing = dir.path().join("missing.jpg");
+        let moves = [(a.clone(), b.clone()), (missing.clone(), a.clone())];
+        let temporary = [staging_path(&b), staging_path(&a)];
+        assert!(move_all_via(&moves, &temporary).is_err());
+        assert_eq!(fs::read_to_string(&a).unwrap(), "b");
+        assert_eq!(fs::read_to_string(&b).unwrap(), "a");
+    }
+
+    #[test]
+    fn staging_path_is_hidden_and_not_a_picture() {
+        let staged = staging_path(Path::new("edit/a.jpg"));
+        assert_eq!(staged, Path::new("edit/.a.jpg.phog-tmp"));
+    }
+
+    #[test]
+    fn failed_move_keeps_the_destination() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("a.jpg");
+        fs::write(&dest, "kept").unwrap();
+        // not a crossing of filesystems, so no copy is tried
+        assert!(move_file(&dir.path().join("missing.jpg"), &dest).is_err());
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "kept");
+    }
+
+    #[test]
+    fn compare_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b, c) = (
+            dir.path().join("a"),
+            dir.path().join("b"),
+            dir.path().join("c"),
+        );
+        fs::write(&a, "same").unwrap();
+        fs::write(&b, "same").unwrap();
+        fs::write(&c, "diff").unwrap();
+        assert!(same_content(&a, &b).unwrap());
+        assert!(!same_content(&a, &c).unwrap());
+    }
+}
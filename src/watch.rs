@@ -0,0 +1,45 @@
+use notify::event::{AccessKind, AccessMode, ModifyKind, RenameMode};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+/// Change in the content of a watched folder
+pub enum FolderChange {
+    /// a file was fully written or moved into the folder
+    Added(PathBuf),
+    /// a file was deleted or moved out of the folder
+    Removed(PathBuf),
+}
+
+/// Starts watching the folder (not its subfolders, nor its hidden files), changes are sent
+/// through the returned channel. Watching stops when the watcher is dropped.
+pub fn watch_folder(
+    folder: &Path,
+) -> notify::Result<(RecommendedWatcher, UnboundedReceiver<FolderChange>)> {
+    let (sender, receiver) = unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        let Ok(event) = res else {
+            return;
+        };
+        let change = match event.kind {
+            // waiting for the file to be closed rather than created so it is not read half written
+            EventKind::Access(AccessKind::Close(AccessMode::Write))
+            | EventKind::Modify(ModifyKind::Name(RenameMode::To)) => FolderChange::Added,
+            EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                FolderChange::Removed
+            }
+            _ => return,
+        };
+        // hidden files are the app's own (session, files being moved or renamed)
+        let hidden = |path: &PathBuf| {
+            path.file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+        };
+        for path in event.paths.into_iter().filter(|path| !hidden(path)) {
+            // receiver only dropped when closing the app
+            let _ = sender.send(change(path));
+        }
+    })?;
+    watcher.watch(folder, RecursiveMode::NonRecursive)?;
+    Ok((watcher, receiver))
+}